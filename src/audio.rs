@@ -0,0 +1,187 @@
+use macroquad::{audio::{Sound, PlaySoundParams, play_sound, stop_sound, set_sound_volume}, rand::gen_range};
+use std::collections::HashMap;
+use crate::timer::{Clock, Scheduler, Timer};
+
+const CROSSFADE_TIME: f64 = 2.0;
+
+pub struct Track {
+    pub sound: Sound,
+    pub length: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Playlist {
+    Menu,
+    Gameplay,
+    GameOver,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sfx {
+    Hit,
+    Pickup,
+}
+
+// What the music schedules on the wall clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Cue {
+    // The current track is about to end, so the next one fades in.
+    NextTrack,
+    // The old track has faded out completely.
+    FadeDone,
+}
+
+// Reads the duration out of a RIFF/WAVE header, so tracks can be any length.
+pub fn wav_duration(bytes: &[u8]) -> Option<f64> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut byte_rate = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let data = offset + 8;
+        if id == b"fmt " && data + 12 <= bytes.len() {
+            byte_rate = Some(u32::from_le_bytes(bytes[data + 8..data + 12].try_into().ok()?));
+        }
+        if id == b"data" {
+            let rate = byte_rate.filter(|rate| *rate > 0)?;
+            return Some(size as f64 / rate as f64);
+        }
+        offset = data + size + size % 2;
+    }
+    None
+}
+
+pub struct AudioManager {
+    tracks: Vec<Track>,
+    sfx: Vec<Sound>,
+    playlists: HashMap<Playlist, Vec<usize>>,
+    playlist: Option<Playlist>,
+    current: Option<usize>,
+    fading_out: Option<usize>,
+    fade: Option<Timer>,
+    schedule: Scheduler<Cue>,
+    last_track: Option<usize>,
+    music_volume: f32,
+    sound_volume: f32,
+}
+
+impl AudioManager {
+    pub fn new(tracks: Vec<Track>, sfx: Vec<Sound>) -> Self {
+        Self {
+            tracks,
+            sfx,
+            playlists: HashMap::new(),
+            playlist: None,
+            current: None,
+            fading_out: None,
+            fade: None,
+            schedule: Scheduler::new(),
+            last_track: None,
+            music_volume: 0.25,
+            sound_volume: 0.25,
+        }
+    }
+
+    pub fn define_playlist(&mut self, playlist: Playlist, tracks: Vec<usize>) {
+        let tracks = tracks.into_iter().filter(|track| *track < self.tracks.len()).collect();
+        self.playlists.insert(playlist, tracks);
+    }
+
+    // Volumes come from the Options screen as 0-100.
+    pub fn set_volumes(&mut self, music_volume: f32, sound_volume: f32) {
+        self.music_volume = (music_volume / 100.0).clamp(0.0, 1.0);
+        self.sound_volume = (sound_volume / 100.0).clamp(0.0, 1.0);
+    }
+
+    pub fn play(&self, sound: Sound) {
+        play_sound(sound, PlaySoundParams { looped: false, volume: self.sound_volume });
+    }
+
+    pub fn play_sfx(&self, sfx: Sfx) {
+        if let Some(sound) = self.sfx.get(sfx as usize) {
+            self.play(*sound);
+        }
+    }
+
+    pub fn set_playlist(&mut self, playlist: Playlist, clock: &impl Clock) {
+        if self.playlist == Some(playlist) {
+            return;
+        }
+        self.playlist = Some(playlist);
+        self.next_track(clock);
+    }
+
+    fn playlist_tracks(&self) -> Option<&Vec<usize>> {
+        self.playlists.get(&self.playlist?)
+    }
+
+    // Shuffles through the current playlist, never playing the same track twice in a row.
+    fn pick_track(&self) -> Option<usize> {
+        let tracks = self.playlist_tracks()?;
+        let choices: Vec<usize> = tracks.iter().copied().filter(|track| tracks.len() == 1 || Some(*track) != self.last_track).collect();
+        if choices.is_empty() {
+            return None;
+        }
+        Some(choices[gen_range(0, choices.len())])
+    }
+
+    fn next_track(&mut self, clock: &impl Clock) {
+        let next = self.pick_track();
+        self.schedule.clear();
+        if let Some(fading) = self.fading_out.take() {
+            stop_sound(self.tracks[fading].sound);
+        }
+        self.fading_out = self.current.take();
+        // A track cannot fade into itself, so it restarts at full volume instead.
+        if let Some(fading) = self.fading_out.take_if(|fading| Some(*fading) == next) {
+            stop_sound(self.tracks[fading].sound);
+        }
+        self.fade = self.fading_out.map(|_| Timer::once(CROSSFADE_TIME, clock));
+        if self.fading_out.is_some() {
+            self.schedule.after(CROSSFADE_TIME, Cue::FadeDone, clock);
+        }
+        if let Some(track) = next {
+            let length = self.tracks[track].length;
+            let volume = if self.fading_out.is_some() { 0.0 } else { self.music_volume };
+            play_sound(self.tracks[track].sound, PlaySoundParams { looped: false, volume });
+            self.schedule.after(length - CROSSFADE_TIME.min(length / 2.0), Cue::NextTrack, clock);
+            self.current = Some(track);
+            self.last_track = Some(track);
+        }
+    }
+
+    fn fade_done(&mut self) {
+        if let Some(fading) = self.fading_out.take() {
+            stop_sound(self.tracks[fading].sound);
+        }
+        self.fade = None;
+    }
+
+    pub fn update(&mut self, clock: &impl Clock) {
+        for cue in self.schedule.poll(clock) {
+            match cue {
+                Cue::FadeDone => self.fade_done(),
+                // Whatever else was due belonged to the track that just ended.
+                Cue::NextTrack => {
+                    self.next_track(clock);
+                    break;
+                },
+            }
+        }
+        // An empty schedule means nothing is playing or fading out, e.g. while the playlist had no tracks.
+        if self.schedule.is_empty() && self.playlist_tracks().is_some_and(|tracks| !tracks.is_empty()) {
+            self.next_track(clock);
+        }
+
+        let fade = self.fade.as_ref().map(|timer| timer.progress(clock) as f32).unwrap_or(1.0);
+        if let Some(fading) = self.fading_out {
+            set_sound_volume(self.tracks[fading].sound, self.music_volume * (1.0 - fade));
+        }
+        if let Some(current) = self.current {
+            set_sound_volume(self.tracks[current].sound, self.music_volume * fade);
+        }
+    }
+}
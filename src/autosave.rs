@@ -0,0 +1,35 @@
+use crate::timer::{Clock, Timer};
+
+pub const DEFAULT_INTERVAL: f64 = 60.0;
+const INDICATOR_TIME: f64 = 2.0;
+
+pub struct Autosave {
+    interval: Timer,
+    indicator: Timer,
+}
+
+impl Autosave {
+    pub fn new(interval: f64, clock: &impl Clock) -> Self {
+        Self { interval: Timer::repeating(interval, clock), indicator: Timer::finished(clock) }
+    }
+
+    pub fn set_interval(&mut self, interval: f64, clock: &impl Clock) {
+        if self.interval.duration != interval {
+            self.interval = Timer::repeating(interval, clock);
+        }
+    }
+
+    // An interval of zero turns interval saves off.
+    pub fn is_due(&mut self, clock: &impl Clock) -> bool {
+        self.interval.duration > 0.0 && self.interval.poll(clock)
+    }
+
+    pub fn mark_saved(&mut self, clock: &impl Clock) {
+        self.interval.restart(clock);
+        self.indicator = Timer::once(INDICATOR_TIME, clock);
+    }
+
+    pub fn show_indicator(&self, clock: &impl Clock) -> bool {
+        !self.indicator.is_done(clock)
+    }
+}
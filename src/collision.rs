@@ -0,0 +1,339 @@
+use macroquad::prelude::{Color, Image, Rect, Vec2, vec2, draw_circle_lines, draw_line};
+use serde::Deserialize;
+
+// How an archetype's collision shape is described in the content file, in sprite pixels.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShapeDef {
+    Circle {
+        radius: f32,
+        #[serde(default)]
+        center: Option<[f32; 2]>,
+    },
+    Polygon {
+        points: Vec<[f32; 2]>,
+    },
+    // Convex hull of the texture's opaque pixels, built when the texture loads.
+    AlphaMask {
+        #[serde(default = "default_threshold")]
+        threshold: u8,
+    },
+}
+
+fn default_threshold() -> u8 {
+    128
+}
+
+// A convex collision shape, either in sprite space or, once transformed, in world space.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Circle { center: Vec2, radius: f32 },
+    Polygon(Vec<Vec2>),
+}
+
+// Sprites rotate around their center, the same way `draw_texture_ex` draws them.
+fn placement(sprite: Rect, rotation: f32) -> impl Fn(Vec2) -> Vec2 {
+    let pivot = vec2(sprite.w / 2.0, sprite.h / 2.0);
+    let origin = sprite.point() + pivot;
+    let (sin, cos) = rotation.sin_cos();
+    move |point: Vec2| {
+        let local = point - pivot;
+        origin + vec2(local.x * cos - local.y * sin, local.x * sin + local.y * cos)
+    }
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Shape::Polygon(Vec::new())
+    }
+}
+
+impl Shape {
+    pub fn rect(rect: Rect) -> Shape {
+        Shape::Polygon(vec![
+            vec2(rect.x, rect.y),
+            vec2(rect.right(), rect.y),
+            vec2(rect.right(), rect.bottom()),
+            vec2(rect.x, rect.bottom()),
+        ])
+    }
+
+    // Places a sprite-space shape into the world.
+    pub fn transformed(&self, sprite: Rect, rotation: f32) -> Shape {
+        let transform = placement(sprite, rotation);
+        match self {
+            Shape::Circle { center, radius } => Shape::Circle { center: transform(*center), radius: *radius },
+            Shape::Polygon(points) => Shape::Polygon(points.iter().map(|point| transform(*point)).collect()),
+        }
+    }
+
+    pub fn is_convex(&self) -> bool {
+        match self {
+            Shape::Circle { radius, .. } => *radius > 0.0,
+            Shape::Polygon(points) => {
+                if points.len() < 3 {
+                    return false;
+                }
+                let mut sign = 0.0;
+                for index in 0..points.len() {
+                    let a = points[index];
+                    let b = points[(index + 1) % points.len()];
+                    let c = points[(index + 2) % points.len()];
+                    let cross = (b - a).perp_dot(c - b);
+                    if cross != 0.0 {
+                        if sign != 0.0 && cross.signum() != sign {
+                            return false;
+                        }
+                        sign = cross.signum();
+                    }
+                }
+                sign != 0.0
+            },
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        match self {
+            Shape::Circle { center, radius } => Rect { x: center.x - radius, y: center.y - radius, w: radius * 2.0, h: radius * 2.0 },
+            Shape::Polygon(points) => {
+                let min = points.iter().fold(vec2(f32::MAX, f32::MAX), |min, point| min.min(*point));
+                let max = points.iter().fold(vec2(f32::MIN, f32::MIN), |max, point| max.max(*point));
+                Rect { x: min.x, y: min.y, w: max.x - min.x, h: max.y - min.y }
+            },
+        }
+    }
+
+    pub fn draw_lines(&self, thickness: f32, color: Color) {
+        match self {
+            Shape::Circle { center, radius } => draw_circle_lines(center.x, center.y, *radius, thickness, color),
+            Shape::Polygon(points) => {
+                for index in 0..points.len() {
+                    let a = points[index];
+                    let b = points[(index + 1) % points.len()];
+                    draw_line(a.x, a.y, b.x, b.y, thickness, color);
+                }
+            },
+        }
+    }
+
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        match self {
+            Shape::Circle { center, radius } => {
+                let middle = center.dot(axis);
+                (middle - radius, middle + radius)
+            },
+            Shape::Polygon(points) => points.iter().fold((f32::MAX, f32::MIN), |(min, max), point| {
+                let projected = point.dot(axis);
+                (min.min(projected), max.max(projected))
+            }),
+        }
+    }
+
+    // Candidate separating axes between `self` and `other` moved by `offset`.
+    fn axes<'a>(&'a self, other: &'a Shape, offset: Vec2) -> impl Iterator<Item = Vec2> + 'a {
+        let (edges, towards) = match self {
+            Shape::Polygon(points) => (points.as_slice(), None),
+            Shape::Circle { center, .. } => {
+                let closest = match other {
+                    Shape::Circle { center: other_center, .. } => *other_center + offset,
+                    Shape::Polygon(points) => points.iter().map(|point| *point + offset)
+                        .min_by(|a, b| a.distance_squared(*center).total_cmp(&b.distance_squared(*center)))
+                        .unwrap_or(*center),
+                };
+                (&[][..], Some(closest - *center))
+            },
+        };
+        (0..edges.len())
+            .map(move |index| (edges[(index + 1) % edges.len()] - edges[index]).perp())
+            .chain(towards)
+            .filter(|axis| axis.length_squared() > 0.0)
+            .map(|axis| axis.normalize())
+    }
+
+    // Separating axis test, valid for convex shapes.
+    pub fn overlaps(&self, other: &Shape) -> bool {
+        if let (Shape::Circle { center, radius }, Shape::Circle { center: other_center, radius: other_radius }) = (self, other) {
+            return center.distance_squared(*other_center) < (radius + other_radius) * (radius + other_radius);
+        }
+        if !self.bounds().overlaps(&other.bounds()) {
+            return false;
+        }
+        for axis in self.axes(other, Vec2::ZERO).chain(other.axes(self, Vec2::ZERO)) {
+            let (min, max) = self.project(axis);
+            let (other_min, other_max) = other.project(axis);
+            if max <= other_min || other_max <= min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Shape {
+    // Moves `self` by `motion` against a resting `other` and returns the fraction of
+    // the move at which they first touch. This is the separating axis test run over
+    // time: on every axis the projections must overlap at some moment, and all of
+    // those moments must overlap each other. Any axis is a valid candidate, so a
+    // few extra ones keep circles and long moves from reporting false contacts.
+    pub fn sweep(&self, motion: Vec2, other: &Shape) -> Option<f32> {
+        if motion.length_squared() <= f32::EPSILON {
+            return if self.overlaps(other) { Some(0.0) } else { None };
+        }
+        let bounds = self.bounds();
+        let moved_bounds = Rect { x: bounds.x + motion.x, y: bounds.y + motion.y, ..bounds };
+        if !bounds.combine_with(moved_bounds).overlaps(&other.bounds()) {
+            return None;
+        }
+        // The axes of `self` at the end of the move are found by moving `other` back instead.
+        let axes = self.axes(other, Vec2::ZERO)
+            .chain(self.axes(other, -motion))
+            .chain(other.axes(self, Vec2::ZERO))
+            .chain(other.axes(self, motion))
+            .chain(std::iter::once(motion.perp().normalize()));
+        let mut enter: f32 = 0.0;
+        let mut exit: f32 = 1.0;
+        for axis in axes {
+            let (min, max) = self.project(axis);
+            let (other_min, other_max) = other.project(axis);
+            let speed = motion.dot(axis);
+            if speed.abs() <= f32::EPSILON {
+                if max <= other_min || other_max <= min {
+                    return None;
+                }
+                continue;
+            }
+            let first = (other_min - max) / speed;
+            let second = (other_max - min) / speed;
+            enter = enter.max(first.min(second));
+            exit = exit.min(first.max(second));
+            if enter >= exit {
+                return None;
+            }
+        }
+        Some(enter)
+    }
+}
+
+// Room to place shapes into the world every tick without allocating. Circles
+// get their own slot so the polygon's points are kept between uses.
+#[derive(Debug, Default)]
+pub struct ShapeScratch {
+    circle: Shape,
+    polygon: Shape,
+}
+
+impl ShapeScratch {
+    pub fn place(&mut self, shape: &Shape, sprite: Rect, rotation: f32) -> &Shape {
+        match shape {
+            Shape::Circle { .. } => {
+                self.circle = shape.transformed(sprite, rotation);
+                &self.circle
+            },
+            Shape::Polygon(points) => {
+                let transform = placement(sprite, rotation);
+                match &mut self.polygon {
+                    Shape::Polygon(buffer) => {
+                        buffer.clear();
+                        buffer.extend(points.iter().map(|point| transform(*point)));
+                    },
+                    polygon => *polygon = shape.transformed(sprite, rotation),
+                }
+                &self.polygon
+            },
+        }
+    }
+}
+
+// Monotone chain hull, returned counter-clockwise in screen space.
+fn convex_hull(mut points: Vec<Vec2>) -> Vec<Vec2> {
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut hull: Vec<Vec2> = Vec::new();
+    for pass in 0..2 {
+        let start = hull.len();
+        let iter: Box<dyn Iterator<Item = &Vec2>> = if pass == 0 { Box::new(points.iter()) } else { Box::new(points.iter().rev()) };
+        for point in iter {
+            while hull.len() >= start + 2 && (hull[hull.len() - 1] - hull[hull.len() - 2]).perp_dot(*point - hull[hull.len() - 1]) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(*point);
+        }
+        hull.pop();
+    }
+    hull
+}
+
+// Builds a hull from the opaque pixels of a sprite, scaled to the size it is drawn at.
+// Only a few dozen rows are sampled, which keeps the hull small enough to test every tick.
+pub fn alpha_hull(image: &Image, size: Vec2, threshold: u8) -> Shape {
+    let width = image.width as usize;
+    let height = image.height as usize;
+    let scale = vec2(size.x / width as f32, size.y / height as f32);
+    let step = (height / 32).max(1);
+    let mut points = Vec::new();
+    let mut rows: Vec<usize> = (0..height).step_by(step).collect();
+    rows.push(height.saturating_sub(1));
+    for y in rows {
+        let opaque = |x: &usize| image.bytes.get((y * width + x) * 4 + 3).is_some_and(|alpha| *alpha >= threshold);
+        if let (Some(left), Some(right)) = ((0..width).find(opaque), (0..width).rev().find(opaque)) {
+            points.push(vec2(left as f32, y as f32) * scale);
+            points.push(vec2(left as f32, (y + step).min(height) as f32) * scale);
+            points.push(vec2((right + 1) as f32, y as f32) * scale);
+            points.push(vec2((right + 1) as f32, (y + step).min(height) as f32) * scale);
+        }
+    }
+    let hull = convex_hull(points);
+    if hull.len() < 3 {
+        return Shape::rect(Rect { x: 0.0, y: 0.0, w: size.x, h: size.y });
+    }
+    Shape::Polygon(hull)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall() -> Shape {
+        Shape::rect(Rect { x: 100.0, y: 0.0, w: 4.0, h: 100.0 })
+    }
+
+    #[test]
+    fn fast_circle_cannot_tunnel_through_thin_wall() {
+        let bullet = Shape::Circle { center: vec2(50.0, 50.0), radius: 5.0 };
+        let motion = vec2(200.0, 0.0);
+        // Clear of the wall both before and after the move.
+        assert!(!bullet.overlaps(&wall()));
+        assert!(!Shape::Circle { center: vec2(250.0, 50.0), radius: 5.0 }.overlaps(&wall()));
+        let time = bullet.sweep(motion, &wall()).expect("the bullet passes through the wall");
+        assert!((time - 45.0 / 200.0).abs() < 1e-4, "touched at {}", time);
+    }
+
+    #[test]
+    fn sweep_misses_beside_the_wall() {
+        let bullet = Shape::Circle { center: vec2(50.0, 150.0), radius: 5.0 };
+        assert_eq!(bullet.sweep(vec2(200.0, 0.0), &wall()), None);
+        assert_eq!(bullet.sweep(vec2(40.0, -100.0), &wall()), None);
+    }
+
+    #[test]
+    fn fast_polygon_cannot_tunnel_through_thin_wall() {
+        let crate_shape = Shape::rect(Rect { x: 20.0, y: 40.0, w: 10.0, h: 10.0 });
+        let time = crate_shape.sweep(vec2(300.0, 20.0), &wall()).expect("the crate passes through the wall");
+        assert!((time - 70.0 / 300.0).abs() < 1e-4, "touched at {}", time);
+    }
+
+    #[test]
+    fn scratch_places_shapes_like_transformed() {
+        let mut scratch = ShapeScratch::default();
+        let sprite = Rect { x: 30.0, y: 60.0, w: 20.0, h: 10.0 };
+        let square = Shape::rect(Rect { x: 2.0, y: 1.0, w: 16.0, h: 8.0 });
+        let ring = Shape::Circle { center: vec2(10.0, 5.0), radius: 4.0 };
+        for rotation in [0.0, 0.7, 2.0] {
+            assert_eq!(*scratch.place(&square, sprite, rotation), square.transformed(sprite, rotation));
+            assert_eq!(*scratch.place(&ring, sprite, rotation), ring.transformed(sprite, rotation));
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// Seconds after a pickup in which the next one keeps the combo going.
+pub const COMBO_WINDOW: f64 = 1.5;
+// Pickups in a row needed for each step up in the multiplier.
+const COMBO_STEP: u32 = 5;
+const MAX_MULTIPLIER: u32 = 5;
+
+// Consecutive pickups, timed on the simulation clock so replays keep the same combos.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Combo {
+    pub count: u32,
+    expires: f64,
+}
+
+impl Combo {
+    // Counts a pickup at `now` and returns the multiplier it scores with.
+    pub fn pickup(&mut self, now: f64) -> u32 {
+        if now > self.expires {
+            self.count = 0;
+        }
+        self.count += 1;
+        self.expires = now + COMBO_WINDOW;
+        self.multiplier()
+    }
+
+    pub fn multiplier(&self) -> u32 {
+        (1 + self.count / COMBO_STEP).min(MAX_MULTIPLIER)
+    }
+
+    pub fn update(&mut self, now: f64) {
+        if now > self.expires {
+            self.count = 0;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.count = 0;
+        self.expires = 0.0;
+    }
+
+    // How much of the window is left before the combo drops, for the meter.
+    pub fn fraction_left(&self, now: f64) -> f32 {
+        ((self.expires - now) / COMBO_WINDOW).clamp(0.0, 1.0) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiplier_steps_up_and_caps() {
+        let mut combo = Combo::default();
+        let multipliers: Vec<u32> = (0..30).map(|pickup| combo.pickup(pickup as f64 * 0.5)).collect();
+        assert_eq!(multipliers[0], 1);
+        assert_eq!(multipliers[3], 1);
+        // The fifth pickup in a row is the first to score double.
+        assert_eq!(multipliers[4], 2);
+        assert_eq!(multipliers[9], 3);
+        assert_eq!(multipliers[29], MAX_MULTIPLIER);
+    }
+
+    #[test]
+    fn combo_drops_after_the_window() {
+        let mut combo = Combo::default();
+        for pickup in 0..5 {
+            combo.pickup(pickup as f64);
+        }
+        assert_eq!(combo.multiplier(), 2);
+        combo.update(4.0 + COMBO_WINDOW);
+        assert_eq!(combo.count, 5);
+        combo.update(4.1 + COMBO_WINDOW);
+        assert_eq!(combo.count, 0);
+        assert_eq!(combo.pickup(10.0), 1);
+    }
+
+    #[test]
+    fn reset_clears_the_combo() {
+        let mut combo = Combo::default();
+        for pickup in 0..7 {
+            combo.pickup(pickup as f64 * 0.1);
+        }
+        combo.reset();
+        assert_eq!(combo.multiplier(), 1);
+        assert_eq!(combo.fraction_left(0.7), 0.0);
+        assert_eq!(combo.pickup(0.8), 1);
+    }
+}
@@ -0,0 +1,433 @@
+use macroquad::{prelude::{Rect, vec2}, rand::RandGenerator};
+use serde::Deserialize;
+use std::{fmt, rc::Rc};
+use crate::{collision::{Shape, ShapeDef}, difficulty::{Preset, Presets}, effects::{EffectDef, EffectKind, Stacking}};
+
+pub const CONTENT_PATH: &str = "res/objects.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Scrap,
+    Asteroid,
+    PowerUp,
+}
+
+fn default_multiplier() -> f32 {
+    1.0
+}
+
+fn default_hit_points() -> i32 {
+    1
+}
+
+// What a destroyed asteroid breaks into.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Fragments {
+    pub archetype: String,
+    pub count: u32,
+    #[serde(skip)]
+    pub archetype_index: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Archetype {
+    pub name: String,
+    pub category: Category,
+    pub texture: String,
+    pub size: [f32; 2],
+    // Offset and size of the hitbox inside the sprite; the whole sprite when left out.
+    #[serde(default)]
+    pub hitbox: Option<[f32; 4]>,
+    // Inclusive range of points awarded on pickup, or for shooting down an asteroid.
+    #[serde(default)]
+    pub points: [i32; 2],
+    // Health the ship loses when it hits this.
+    #[serde(default)]
+    pub damage: i32,
+    // Cannon hits an asteroid takes before it breaks.
+    #[serde(default = "default_hit_points")]
+    pub hit_points: i32,
+    #[serde(default)]
+    pub fragments: Option<Fragments>,
+    #[serde(default = "default_multiplier")]
+    pub fall_speed: f32,
+    // Degrees per second.
+    #[serde(default)]
+    pub rotation_speed: f32,
+    #[serde(default = "default_multiplier")]
+    pub spawn_weight: f32,
+    #[serde(default)]
+    pub sound: Option<String>,
+    // What a power-up does when picked up. Only power-ups have one.
+    #[serde(default)]
+    pub effect: Option<EffectKind>,
+    // Takes priority over `hitbox` when set.
+    #[serde(default)]
+    pub shape: Option<ShapeDef>,
+    // Shared with every object spawned as this archetype.
+    #[serde(skip)]
+    pub collision: Rc<Shape>,
+}
+
+impl Archetype {
+    pub fn hitbox(&self) -> Rect {
+        match self.hitbox {
+            Some([x, y, w, h]) => Rect { x, y, w, h },
+            None => Rect { x: 0.0, y: 0.0, w: self.size[0], h: self.size[1] },
+        }
+    }
+}
+
+fn default_player_texture() -> String {
+    String::from("res/player.png")
+}
+
+fn default_player_size() -> [f32; 2] {
+    [75.0, 125.0]
+}
+
+fn default_invulnerability() -> f64 {
+    1.0
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PlayerDef {
+    #[serde(default = "default_player_texture")]
+    pub texture: String,
+    #[serde(default = "default_player_size")]
+    pub size: [f32; 2],
+    #[serde(default)]
+    pub shape: Option<ShapeDef>,
+    // Seconds after a hit during which asteroids pass through the ship.
+    #[serde(default = "default_invulnerability")]
+    pub invulnerability: f64,
+    #[serde(skip)]
+    pub collision: Shape,
+}
+
+impl Default for PlayerDef {
+    fn default() -> Self {
+        Self { texture: default_player_texture(), size: default_player_size(), shape: None, invulnerability: default_invulnerability(), collision: Shape::default() }
+    }
+}
+
+// An achievement that gives a skin away for free.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Unlock {
+    // Score at least this much in one endless run.
+    HighScore { points: i32 },
+    // Collect this many scraps over all runs.
+    LifetimeScraps { count: u64 },
+    // Finish this many runs.
+    Runs { count: u32 },
+    // Beat the named level.
+    Level { name: String },
+}
+
+impl Unlock {
+    pub fn description(&self) -> String {
+        match self {
+            Unlock::HighScore { points } => format!("Score {} points in one run", points),
+            Unlock::LifetimeScraps { count } => format!("Collect {} scraps", count),
+            Unlock::Runs { count } => format!("Finish {} runs", count),
+            Unlock::Level { name } => format!("Beat {}", name),
+        }
+    }
+}
+
+// A cosmetic ship sprite. Skins share the player's size and collision shape.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Skin {
+    pub name: String,
+    pub texture: String,
+    // RGB exhaust trail drawn behind the ship, if any.
+    #[serde(default)]
+    pub trail: Option<[u8; 3]>,
+    // Coins to buy it. Free skins with no unlock are owned from the start.
+    #[serde(default)]
+    pub price: i32,
+    #[serde(default)]
+    pub unlock: Option<Unlock>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Content {
+    #[serde(default)]
+    pub player: PlayerDef,
+    pub archetypes: Vec<Archetype>,
+    // The first skin is the default one. Left out, the player's texture is used.
+    #[serde(default)]
+    pub skins: Vec<Skin>,
+    // How long each power-up lasts and how it stacks, one per kind.
+    #[serde(default)]
+    pub effects: Vec<EffectDef>,
+    // Chance that an endless run spawns a power-up in place of a scrap.
+    #[serde(default)]
+    pub power_up_chance: f32,
+    // How endless runs ramp up on each preset.
+    pub difficulty: Presets,
+}
+
+// Alpha masks need the texture, so they start out as the hitbox and are replaced once it is loaded.
+fn resolve_shape(def: &Option<ShapeDef>, hitbox: Rect) -> Shape {
+    match def {
+        Some(ShapeDef::Circle { radius, center }) => {
+            let center = center.map(|[x, y]| vec2(x, y)).unwrap_or(hitbox.center());
+            Shape::Circle { center, radius: *radius }
+        },
+        Some(ShapeDef::Polygon { points }) => Shape::Polygon(points.iter().map(|[x, y]| vec2(*x, *y)).collect()),
+        Some(ShapeDef::AlphaMask { .. }) | None => Shape::rect(hitbox),
+    }
+}
+
+pub fn alpha_threshold(def: &Option<ShapeDef>) -> Option<u8> {
+    match def {
+        Some(ShapeDef::AlphaMask { threshold }) => Some(*threshold),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum ContentError {
+    Parse(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContentError::Parse(error) => write!(f, "could not parse content file: {}", error),
+            ContentError::Invalid(reason) => write!(f, "invalid content file: {}", reason),
+        }
+    }
+}
+
+impl Content {
+    pub fn parse(contents: &str) -> Result<Content, ContentError> {
+        let mut content: Content = serde_json::from_str(contents).map_err(ContentError::Parse)?;
+        let player_box = Rect { x: 0.0, y: 0.0, w: content.player.size[0], h: content.player.size[1] };
+        content.player.collision = resolve_shape(&content.player.shape, player_box);
+        for archetype in content.archetypes.iter_mut() {
+            archetype.collision = Rc::new(resolve_shape(&archetype.shape, archetype.hitbox()));
+        }
+        let names: Vec<String> = content.archetypes.iter().map(|archetype| archetype.name.clone()).collect();
+        for archetype in content.archetypes.iter_mut() {
+            if let Some(fragments) = archetype.fragments.as_mut() {
+                fragments.archetype_index = match names.iter().position(|name| *name == fragments.archetype) {
+                    Some(index) => index,
+                    None => return Err(ContentError::Invalid(format!("archetype '{}': unknown fragment archetype '{}'", archetype.name, fragments.archetype))),
+                };
+            }
+        }
+        if content.skins.is_empty() {
+            content.skins.push(Skin { name: String::from("Classic"), texture: content.player.texture.clone(), trail: None, price: 0, unlock: None });
+        }
+        content.validate()?;
+        Ok(content)
+    }
+
+    fn validate(&self) -> Result<(), ContentError> {
+        if self.player.size.iter().any(|side| !side.is_finite() || *side <= 0.0) {
+            return Err(ContentError::Invalid(String::from("player size must be positive")));
+        }
+        if !self.player.collision.is_convex() {
+            return Err(ContentError::Invalid(String::from("player shape must be a convex polygon or a circle")));
+        }
+        if !self.player.invulnerability.is_finite() || self.player.invulnerability < 0.0 {
+            return Err(ContentError::Invalid(String::from("player invulnerability cannot be negative")));
+        }
+        for (index, archetype) in self.archetypes.iter().enumerate() {
+            let invalid = |reason: &str| Err(ContentError::Invalid(format!("archetype '{}': {}", archetype.name, reason)));
+            if archetype.name.is_empty() {
+                return Err(ContentError::Invalid(format!("archetype #{} has no name", index)));
+            }
+            if self.archetypes[..index].iter().any(|other| other.name == archetype.name) {
+                return invalid("name is used more than once");
+            }
+            if archetype.texture.is_empty() {
+                return invalid("texture path is empty");
+            }
+            if archetype.size.iter().any(|side| !side.is_finite() || *side <= 0.0) {
+                return invalid("size must be positive");
+            }
+            let hitbox = archetype.hitbox();
+            if hitbox.w <= 0.0 || hitbox.h <= 0.0 || hitbox.x < 0.0 || hitbox.y < 0.0
+                || hitbox.right() > archetype.size[0] || hitbox.bottom() > archetype.size[1] {
+                return invalid("hitbox must be non-empty and inside the sprite");
+            }
+            if !archetype.collision.is_convex() {
+                return invalid("shape must be a convex polygon or a circle");
+            }
+            if archetype.points[0] > archetype.points[1] {
+                return invalid("points range is reversed");
+            }
+            if archetype.damage < 0 {
+                return invalid("damage cannot be negative");
+            }
+            if archetype.hit_points < 1 {
+                return invalid("hit_points must be at least 1");
+            }
+            if let Some(fragments) = &archetype.fragments {
+                let fragment = &self.archetypes[fragments.archetype_index];
+                // Fragments have to shrink, so splitting always comes to an end.
+                if archetype.category != Category::Asteroid || fragment.category != Category::Asteroid {
+                    return invalid("only asteroids can break into fragments, and only into asteroids");
+                }
+                if fragment.size[0] * fragment.size[1] >= archetype.size[0] * archetype.size[1] {
+                    return invalid("fragments must be smaller than the asteroid they come from");
+                }
+                if fragments.count == 0 || fragments.count > 4 {
+                    return invalid("fragment count must be between 1 and 4");
+                }
+            }
+            if !archetype.fall_speed.is_finite() || archetype.fall_speed <= 0.0 {
+                return invalid("fall_speed must be positive");
+            }
+            if !archetype.rotation_speed.is_finite() {
+                return invalid("rotation_speed must be a number");
+            }
+            if !archetype.spawn_weight.is_finite() || archetype.spawn_weight < 0.0 {
+                return invalid("spawn_weight cannot be negative");
+            }
+            match (archetype.category, archetype.effect) {
+                (Category::PowerUp, None) => return invalid("power-ups need an effect"),
+                (Category::PowerUp, Some(kind)) if self.effect(kind).is_none() => return invalid("effect has no entry in effects"),
+                (Category::Scrap | Category::Asteroid, Some(_)) => return invalid("only power-ups can have an effect"),
+                _ => {},
+            }
+        }
+        for (index, skin) in self.skins.iter().enumerate() {
+            if skin.name.is_empty() || skin.texture.is_empty() {
+                return Err(ContentError::Invalid(format!("skin #{} needs a name and a texture", index)));
+            }
+            if self.skins[..index].iter().any(|other| other.name == skin.name) {
+                return Err(ContentError::Invalid(format!("skin '{}' is defined more than once", skin.name)));
+            }
+            if skin.price < 0 {
+                return Err(ContentError::Invalid(format!("skin '{}': price cannot be negative", skin.name)));
+            }
+        }
+        for (index, effect) in self.effects.iter().enumerate() {
+            let invalid = |reason: &str| Err(ContentError::Invalid(format!("effect {:?}: {}", effect.kind, reason)));
+            if self.effects[..index].iter().any(|other| other.kind == effect.kind) {
+                return invalid("defined more than once");
+            }
+            if !effect.duration.is_finite() || effect.duration <= 0.0 {
+                return invalid("duration must be positive");
+            }
+            let strength_valid = match effect.kind {
+                EffectKind::Shield => true,
+                EffectKind::SlowMotion => effect.strength > 0.0 && effect.strength < 1.0,
+                EffectKind::Magnet | EffectKind::ScoreMultiplier => effect.strength.is_finite() && effect.strength > 0.0,
+            };
+            if !strength_valid {
+                return invalid("strength is out of range");
+            }
+            match effect.stacking {
+                Stacking::Extend { max } if max < effect.duration => return invalid("extend max must be at least the duration"),
+                Stacking::Stack { max: 0 } => return invalid("stack max must be at least 1"),
+                _ => {},
+            }
+        }
+        for preset in Preset::ALL {
+            for (name, curve) in self.difficulty.get(preset).curves() {
+                if let Err(reason) = curve.validate() {
+                    return Err(ContentError::Invalid(format!("{} difficulty, {}: {}", preset.label(), name, reason)));
+                }
+            }
+        }
+        if !(0.0..=1.0).contains(&self.power_up_chance) {
+            return Err(ContentError::Invalid(String::from("power_up_chance must be between 0 and 1")));
+        }
+        if self.power_up_chance > 0.0 && self.total_weight(Category::PowerUp) <= 0.0 {
+            return Err(ContentError::Invalid(String::from("power_up_chance is set but there is no spawnable power-up")));
+        }
+        for category in [Category::Scrap, Category::Asteroid] {
+            if self.total_weight(category) <= 0.0 {
+                return Err(ContentError::Invalid(format!("no spawnable {:?} archetype", category)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn effect(&self, kind: EffectKind) -> Option<&EffectDef> {
+        self.effects.iter().find(|effect| effect.kind == kind)
+    }
+
+    fn total_weight(&self, category: Category) -> f32 {
+        self.archetypes.iter().filter(|archetype| archetype.category == category).map(|archetype| archetype.spawn_weight).sum()
+    }
+
+    // Picks an archetype index of the given category, weighted by `spawn_weight`.
+    pub fn pick(&self, category: Category, rng: &RandGenerator) -> usize {
+        let mut roll = rng.gen_range(0.0, self.total_weight(category));
+        let mut last = 0;
+        for (index, archetype) in self.archetypes.iter().enumerate() {
+            if archetype.category != category || archetype.spawn_weight <= 0.0 {
+                continue;
+            }
+            if roll < archetype.spawn_weight {
+                return index;
+            }
+            roll -= archetype.spawn_weight;
+            last = index;
+        }
+        last
+    }
+}
+
+// Content with just the given archetypes, and every preset ramping like Normal.
+#[cfg(test)]
+pub fn test_content(archetypes: &str) -> Content {
+    let curves = r#"{
+        "player_speed": { "type": "piecewise", "driver": "score", "points": [[0, 250], [1625, 3500]] },
+        "fall_speed": { "type": "piecewise", "driver": "score", "points": [[0, 125], [1625, 1750]] },
+        "spawn_density": { "type": "constant", "value": 32 },
+        "asteroid_ratio": { "type": "constant", "value": 0.23 }
+    }"#;
+    let json = format!(r#"{{ "archetypes": {archetypes}, "difficulty": {{ "easy": {curves}, "normal": {curves}, "hard": {curves}, "endless": {curves} }} }}"#);
+    Content::parse(&json).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEIGHTED: &str = r#"[
+        { "name": "bolt", "category": "scrap", "texture": "bolt.png", "size": [10, 10], "spawn_weight": 1 },
+        { "name": "gear", "category": "scrap", "texture": "gear.png", "size": [10, 10], "spawn_weight": 3 },
+        { "name": "rock", "category": "asteroid", "texture": "rock.png", "size": [10, 10], "spawn_weight": 5 },
+        { "name": "unused", "category": "scrap", "texture": "unused.png", "size": [10, 10], "spawn_weight": 0 },
+        { "name": "plate", "category": "scrap", "texture": "plate.png", "size": [10, 10], "spawn_weight": 6 }
+    ]"#;
+
+    #[test]
+    fn pick_follows_spawn_weights() {
+        let content = test_content(WEIGHTED);
+        let rng = RandGenerator::new();
+        rng.srand(42);
+        let rolls = 100_000;
+        let mut counts = vec![0u32; content.archetypes.len()];
+        for _ in 0..rolls {
+            counts[content.pick(Category::Scrap, &rng)] += 1;
+        }
+        let total = content.total_weight(Category::Scrap);
+        for (archetype, count) in content.archetypes.iter().zip(counts) {
+            let expected = if archetype.category == Category::Scrap { archetype.spawn_weight / total } else { 0.0 };
+            let frequency = count as f32 / rolls as f32;
+            assert!((frequency - expected).abs() < 0.01, "{} picked {} of the time, expected {}", archetype.name, frequency, expected);
+            if expected == 0.0 {
+                assert_eq!(count, 0, "{} should never be picked", archetype.name);
+            }
+        }
+    }
+
+    #[test]
+    fn shipped_content_is_valid() {
+        let content = Content::parse(include_str!("../res/objects.json")).unwrap();
+        let progress = crate::difficulty::Progress { score: 100.0, time: 0.0 };
+        assert_eq!(content.difficulty.get(Preset::Normal).player_speed.value(progress), 450.0);
+    }
+}
@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+
+// How far into a run the player is. Curves read one of these as their input.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Progress {
+    pub score: f32,
+    pub time: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Driver {
+    Score,
+    Time,
+}
+
+impl Driver {
+    fn read(&self, progress: Progress) -> f32 {
+        match self {
+            Driver::Score => progress.score,
+            Driver::Time => progress.time,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Curve {
+    Constant { value: f32 },
+    // Straight lines between [input, value] points, held flat before the first and after the last.
+    Piecewise { driver: Driver, points: Vec<(f32, f32)> },
+    // `start * e^(rate * input)`, never going past `max`.
+    Exponential { driver: Driver, start: f32, rate: f32, max: f32 },
+}
+
+impl Curve {
+    pub fn value(&self, progress: Progress) -> f32 {
+        match self {
+            Curve::Constant { value } => *value,
+            Curve::Piecewise { driver, points } => {
+                let input = driver.read(progress);
+                let (first, last) = match (points.first(), points.last()) {
+                    (Some(first), Some(last)) => (*first, *last),
+                    _ => return 0.0,
+                };
+                if input <= first.0 {
+                    return first.1;
+                }
+                for pair in points.windows(2) {
+                    let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+                    if input <= x1 {
+                        if x1 <= x0 {
+                            return y1;
+                        }
+                        return y0 + (y1 - y0) * (input - x0) / (x1 - x0);
+                    }
+                }
+                last.1
+            },
+            Curve::Exponential { driver, start, rate, max } => (start * (rate * driver.read(progress)).exp()).min(*max),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let finite = match self {
+            Curve::Constant { value } => value.is_finite(),
+            Curve::Piecewise { points, .. } => {
+                if points.is_empty() {
+                    return Err("needs at least one point");
+                }
+                if points.windows(2).any(|pair| pair[1].0 < pair[0].0) {
+                    return Err("points must be in order of their input");
+                }
+                points.iter().all(|(input, value)| input.is_finite() && value.is_finite())
+            },
+            Curve::Exponential { start, rate, max, .. } => start.is_finite() && rate.is_finite() && max.is_finite(),
+        };
+        if !finite {
+            return Err("every number must be finite");
+        }
+        Ok(())
+    }
+}
+
+// Everything that scales as a run goes on. Speeds are in pixels per second,
+// `spawn_density` is roughly how many objects are falling at once and
+// `asteroid_ratio` is the share of spawns that are asteroids.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Difficulty {
+    pub player_speed: Curve,
+    pub fall_speed: Curve,
+    pub spawn_density: Curve,
+    pub asteroid_ratio: Curve,
+}
+
+impl Difficulty {
+    pub fn curves(&self) -> [(&'static str, &Curve); 4] {
+        [
+            ("player_speed", &self.player_speed),
+            ("fall_speed", &self.fall_speed),
+            ("spawn_density", &self.spawn_density),
+            ("asteroid_ratio", &self.asteroid_ratio),
+        ]
+    }
+}
+
+// The curves behind each preset, loaded from the content file. Normal keeps the
+// original speed formula, `points * 2 + 250` capped at 3500, with objects falling
+// at half that, and Endless ramps with time survived instead of score.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Presets {
+    pub easy: Difficulty,
+    pub normal: Difficulty,
+    pub hard: Difficulty,
+    pub endless: Difficulty,
+}
+
+impl Presets {
+    pub fn get(&self, preset: Preset) -> &Difficulty {
+        match preset {
+            Preset::Easy => &self.easy,
+            Preset::Normal => &self.normal,
+            Preset::Hard => &self.hard,
+            Preset::Endless => &self.endless,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum Preset {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Endless,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [Preset::Easy, Preset::Normal, Preset::Hard, Preset::Endless];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Preset::Easy => "Easy",
+            Preset::Normal => "Normal",
+            Preset::Hard => "Hard",
+            Preset::Endless => "Endless",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_score(score: f32) -> Progress {
+        Progress { score, time: 0.0 }
+    }
+
+    #[test]
+    fn piecewise_interpolates_between_points() {
+        let curve = Curve::Piecewise { driver: Driver::Score, points: vec![(0.0, 100.0), (100.0, 300.0), (300.0, 400.0)] };
+        assert_eq!(curve.value(at_score(0.0)), 100.0);
+        assert_eq!(curve.value(at_score(50.0)), 200.0);
+        assert_eq!(curve.value(at_score(100.0)), 300.0);
+        assert_eq!(curve.value(at_score(200.0)), 350.0);
+    }
+
+    #[test]
+    fn piecewise_holds_flat_past_the_ends() {
+        let curve = Curve::Piecewise { driver: Driver::Time, points: vec![(10.0, 1.0), (20.0, 2.0)] };
+        assert_eq!(curve.value(Progress { score: 500.0, time: 0.0 }), 1.0);
+        assert_eq!(curve.value(Progress { score: 0.0, time: -5.0 }), 1.0);
+        assert_eq!(curve.value(Progress { score: 0.0, time: 1000.0 }), 2.0);
+        assert_eq!(Curve::Piecewise { driver: Driver::Time, points: Vec::new() }.value(Progress::default()), 0.0);
+    }
+
+    #[test]
+    fn exponential_stops_at_max() {
+        let curve = Curve::Exponential { driver: Driver::Score, start: 100.0, rate: 0.01, max: 500.0 };
+        assert_eq!(curve.value(at_score(0.0)), 100.0);
+        assert!((curve.value(at_score(100.0)) - 100.0 * 1f32.exp()).abs() < 1e-3);
+        assert_eq!(curve.value(at_score(10_000.0)), 500.0);
+    }
+
+    #[test]
+    fn curves_load_from_json() {
+        let curve: Curve = serde_json::from_str(r#"{ "type": "piecewise", "driver": "score", "points": [[0, 250], [1625, 3500]] }"#).unwrap();
+        assert_eq!(curve, Curve::Piecewise { driver: Driver::Score, points: vec![(0.0, 250.0), (1625.0, 3500.0)] });
+        assert!(curve.validate().is_ok());
+        let reversed: Curve = serde_json::from_str(r#"{ "type": "piecewise", "driver": "time", "points": [[10, 1], [5, 2]] }"#).unwrap();
+        assert!(reversed.validate().is_err());
+    }
+}
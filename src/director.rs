@@ -0,0 +1,306 @@
+use macroquad::{prelude::Vec2, rand::RandGenerator};
+use crate::{content::Category, difficulty::{Difficulty, Progress}, timer::Clock};
+
+pub const FIRST_WAVE_DELAY: f64 = 1.0;
+// Spawns waiting to happen. Waves that would go past this are cut short rather than growing the list.
+const MAX_PENDING: usize = 64;
+// How much wider than the ship the safe lane is.
+const LANE_MARGIN: f32 = 1.5;
+// Only this share of the time until the next wave is counted when moving the
+// lane, so the player never needs perfect reactions to reach it.
+const LANE_REACH: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pattern {
+    // A row across the screen, all at once.
+    Line,
+    // Scraps in a V pointing down, centered on the safe lane.
+    VFormation,
+    // Random objects spread out over the time until the next wave.
+    Shower,
+    // A wall of asteroids with only the safe lane left open.
+    Corridor,
+}
+
+impl Pattern {
+    pub const ALL: [Pattern; 4] = [Pattern::Line, Pattern::VFormation, Pattern::Shower, Pattern::Corridor];
+}
+
+// `x` is where the object's center should enter the screen.
+#[derive(Clone, Copy, Debug)]
+pub struct Spawn {
+    pub category: Category,
+    pub x: f32,
+    at: f64,
+}
+
+// What the director needs to know about the world when planning a wave.
+pub struct Arena {
+    pub world: Vec2,
+    pub ship_width: f32,
+    // Where the ship's center is, which is where the first lane starts.
+    pub ship_x: f32,
+    // The widest asteroid, which decides how far spawns get pushed inside the screen.
+    pub object_width: f32,
+    // How far any asteroid's shape can reach from its center while spinning.
+    pub object_reach: f32,
+    // The columns asteroids already in play cover for the rest of their fall.
+    pub asteroids: Vec<(f32, f32)>,
+}
+
+// Everything between `left` and `right` at the ship's height, from the
+// previous lane to the current one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Band {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl Band {
+    pub fn blocks(&self, (left, right): (f32, f32)) -> bool {
+        left < self.right && right > self.left
+    }
+}
+
+// The columns an asteroid spawned at `x` can cover: it is pulled inside the
+// screen first, and can spin out to `object_reach` from its center.
+fn spawn_column(x: f32, arena: &Arena) -> (f32, f32) {
+    let half = arena.object_width / 2.0;
+    let inside = x.clamp(half, (arena.world.x - half).max(half));
+    (x.min(inside) - arena.object_reach, x.max(inside) + arena.object_reach)
+}
+
+// Decides what falls and when. Waves are planned ahead of time into a fixed
+// size list, and every wave moves a safe lane at most as far as the ship can
+// go before the next wave. The lane only moves until it would run into an
+// asteroid that is falling or waiting to spawn, and no asteroid of the wave,
+// nor any fragment of one that is shot, may enter the band between the old
+// and the new lane. Asteroids never leave their column except fragments, so
+// a ship that follows the lane can never be hit.
+pub struct Director {
+    pending: Vec<Spawn>,
+    lane: Option<f32>,
+    band: Option<Band>,
+    pattern: Option<Pattern>,
+}
+
+impl Director {
+    pub fn new() -> Self {
+        Self { pending: Vec::with_capacity(MAX_PENDING), lane: None, band: None, pattern: None }
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.lane = None;
+        self.band = None;
+        self.pattern = None;
+    }
+
+    pub fn pattern(&self) -> Option<Pattern> {
+        self.pattern
+    }
+
+    // Where the ship is safe, once the first wave is planned.
+    pub fn lane(&self) -> Option<f32> {
+        self.lane
+    }
+
+    // What has to stay clear of asteroids until the next wave.
+    pub fn band(&self) -> Option<Band> {
+        self.band
+    }
+
+    // Plans the wave that is due now and returns how many seconds to wait before the next one.
+    pub fn plan_wave(&mut self, difficulty: &Difficulty, progress: Progress, arena: &Arena, rng: &RandGenerator, clock: &impl Clock) -> f64 {
+        let fall_speed = difficulty.fall_speed.value(progress).max(1.0);
+        let density = difficulty.spawn_density.value(progress).max(1.0);
+        let ratio = difficulty.asteroid_ratio.value(progress).clamp(0.0, 1.0);
+        // Objects stay on screen for `world.y / fall_speed` seconds, so spawning this
+        // many per second keeps about `density` of them in play.
+        let rate = density * fall_speed / arena.world.y.max(1.0);
+        let size = (density / 4.0).round().clamp(3.0, 12.0) as usize;
+        let gap = size as f32 / rate;
+
+        let lane_half = arena.ship_width * LANE_MARGIN / 2.0;
+        let lane_min = lane_half.min(arena.world.x / 2.0);
+        let lane_max = (arena.world.x - lane_half).max(lane_min);
+        let reach = difficulty.player_speed.value(progress) * gap * LANE_REACH;
+        let from = self.lane.unwrap_or(arena.ship_x).clamp(lane_min, lane_max);
+        let target = (from + rng.gen_range(-reach, reach)).clamp(lane_min, lane_max);
+        let lane = self.clear_path(from, target, lane_half, arena);
+        self.lane = Some(lane);
+        self.band = Some(Band { left: from.min(lane) - lane_half, right: from.max(lane) + lane_half });
+
+        let now = clock.now();
+        let pattern = Pattern::ALL[rng.gen_range(0, Pattern::ALL.len())];
+        self.pattern = Some(pattern);
+        match pattern {
+            Pattern::Line => {
+                let spacing = arena.world.x / size as f32;
+                for slot in 0..size {
+                    let category = if rng.gen_range(0.0, 1.0) < ratio { Category::Asteroid } else { Category::Scrap };
+                    self.push(category, spacing * (slot as f32 + 0.5), now, arena);
+                }
+            },
+            Pattern::VFormation => {
+                let spacing = arena.object_width;
+                let step = (spacing / fall_speed) as f64;
+                self.push(Category::Scrap, lane, now, arena);
+                for arm in 1..=size / 2 {
+                    let offset = spacing * arm as f32;
+                    let at = now + step * arm as f64;
+                    // Only the tips of the V can be asteroids.
+                    let category = if arm == size / 2 && rng.gen_range(0.0, 1.0) < ratio { Category::Asteroid } else { Category::Scrap };
+                    for x in [lane - offset, lane + offset] {
+                        if x > 0.0 && x < arena.world.x {
+                            self.push(category, x, at, arena);
+                        }
+                    }
+                }
+            },
+            Pattern::Shower => {
+                for _ in 0..size * 2 {
+                    let category = if rng.gen_range(0.0, 1.0) < ratio { Category::Asteroid } else { Category::Scrap };
+                    let at = now + rng.gen_range(0.0, gap) as f64;
+                    self.push(category, rng.gen_range(0.0, arena.world.x), at, arena);
+                }
+            },
+            Pattern::Corridor => {
+                let spacing = arena.object_width * 1.5;
+                let mut x = arena.object_width / 2.0;
+                while x < arena.world.x {
+                    self.push(Category::Asteroid, x, now, arena);
+                    x += spacing;
+                }
+                self.push(Category::Scrap, lane, now + (spacing / fall_speed) as f64, arena);
+            },
+        }
+        gap as f64
+    }
+
+    // Moves from `from` towards `to`, stopping where the lane would start to
+    // overlap an asteroid that is falling or waiting to spawn.
+    fn clear_path(&self, from: f32, to: f32, lane_half: f32, arena: &Arena) -> f32 {
+        let pending = self.pending.iter()
+            .filter(|spawn| spawn.category == Category::Asteroid)
+            .map(|spawn| spawn_column(spawn.x, arena));
+        arena.asteroids.iter().copied().chain(pending).fold(to, |lane, (left, right)| {
+            if to > from && left - lane_half >= from {
+                lane.min(left - lane_half)
+            }
+            else if to < from && right + lane_half <= from {
+                lane.max(right + lane_half)
+            }
+            else {
+                lane
+            }
+        })
+    }
+
+    // Asteroids that would cross the band are dropped, or become scraps when
+    // they are right in the lane.
+    fn push(&mut self, category: Category, x: f32, at: f64, arena: &Arena) {
+        if self.pending.len() >= MAX_PENDING {
+            return;
+        }
+        if category == Category::Asteroid && self.band.is_some_and(|band| band.blocks(spawn_column(x, arena))) {
+            let lane_half = arena.ship_width * LANE_MARGIN / 2.0;
+            if self.lane.is_some_and(|lane| (x - lane).abs() < lane_half) {
+                self.pending.push(Spawn { category: Category::Scrap, x, at });
+            }
+            return;
+        }
+        self.pending.push(Spawn { category, x, at });
+    }
+
+    // Hands out the next spawn that is due, if any.
+    pub fn next_spawn(&mut self, clock: &impl Clock) -> Option<Spawn> {
+        let now = clock.now();
+        let index = self.pending.iter().position(|spawn| spawn.at <= now)?;
+        Some(self.pending.swap_remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{difficulty::Curve, timer::ManualClock};
+
+    const WORLD: Vec2 = Vec2::new(800.0, 600.0);
+    const SHIP_WIDTH: f32 = 75.0;
+    const FALL_SPEED: f32 = 300.0;
+    const PLAYER_SPEED: f32 = 250.0;
+
+    fn difficulty() -> Difficulty {
+        Difficulty {
+            player_speed: Curve::Constant { value: PLAYER_SPEED },
+            fall_speed: Curve::Constant { value: FALL_SPEED },
+            spawn_density: Curve::Constant { value: 48.0 },
+            asteroid_ratio: Curve::Constant { value: 0.6 },
+        }
+    }
+
+    // A little narrower, so a band that only touches an asteroid doesn't count as blocked.
+    fn shrunk(band: Band) -> Band {
+        Band { left: band.left + 0.01, right: band.right - 0.01 }
+    }
+
+    #[test]
+    fn lane_stays_clear_of_every_asteroid() {
+        let difficulty = difficulty();
+        let progress = Progress { score: 0.0, time: 0.0 };
+        let rng = RandGenerator::new();
+        rng.srand(7);
+        let mut clock = ManualClock::new(0.0);
+        let mut director = Director::new();
+        // Columns of the asteroids in play, and when they leave the screen.
+        let mut falling: Vec<((f32, f32), f64)> = Vec::new();
+        let mut next_wave = FIRST_WAVE_DELAY;
+        let (mut moves, mut asteroids) = (0, 0);
+        while clock.now() < 120.0 {
+            clock.advance(1.0 / 60.0);
+            let now = clock.now();
+            falling.retain(|(_, leaves)| *leaves > now);
+            let arena = Arena {
+                world: WORLD,
+                ship_width: SHIP_WIDTH,
+                ship_x: WORLD.x / 2.0,
+                object_width: 60.0,
+                object_reach: 60.0f32.hypot(60.0) / 2.0,
+                asteroids: falling.iter().map(|(column, _)| *column).collect(),
+            };
+            if now >= next_wave {
+                let previous = director.lane();
+                let gap = director.plan_wave(&difficulty, progress, &arena, &rng, &clock);
+                next_wave = now + gap;
+                let (lane, band) = (director.lane().unwrap(), shrunk(director.band().unwrap()));
+                if let Some(previous) = previous {
+                    assert!((lane - previous).abs() <= PLAYER_SPEED * gap as f32 * LANE_REACH + 0.01);
+                    moves += (lane != previous) as u32;
+                }
+                assert!(band.left <= lane && lane <= band.right);
+                for column in arena.asteroids.iter() {
+                    assert!(!band.blocks(*column), "lane moved into {:?}", column);
+                }
+            }
+            let band = shrunk(director.band().unwrap_or(Band { left: 0.0, right: 0.0 }));
+            while let Some(spawn) = director.next_spawn(&clock) {
+                if spawn.category == Category::Asteroid {
+                    let column = spawn_column(spawn.x, &arena);
+                    assert!(!band.blocks(column), "asteroid at {} spawned into {:?}", spawn.x, band);
+                    falling.push((column, now + ((WORLD.y + 60.0) / FALL_SPEED) as f64));
+                    asteroids += 1;
+                }
+            }
+        }
+        assert!(moves > 100, "the lane only moved {} times", moves);
+        assert!(asteroids > 1000, "only {} asteroids fell", asteroids);
+    }
+
+    #[test]
+    fn spawns_near_the_edge_count_where_they_end_up() {
+        let arena = Arena { world: WORLD, ship_width: SHIP_WIDTH, ship_x: 0.0, object_width: 60.0, object_reach: 40.0, asteroids: Vec::new() };
+        assert_eq!(spawn_column(0.0, &arena), (-40.0, 70.0));
+        assert_eq!(spawn_column(400.0, &arena), (360.0, 440.0));
+    }
+}
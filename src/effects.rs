@@ -0,0 +1,229 @@
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EffectKind {
+    // Each stack absorbs one asteroid hit.
+    Shield,
+    // Pulls scraps within `strength` pixels per stack.
+    Magnet,
+    // Falling objects move at `strength` times their speed, once per stack.
+    SlowMotion,
+    // Scrap is worth `strength` times as much, plus `strength - 1` per extra stack.
+    ScoreMultiplier,
+}
+
+impl EffectKind {
+    pub const ALL: [EffectKind; 4] = [EffectKind::Shield, EffectKind::Magnet, EffectKind::SlowMotion, EffectKind::ScoreMultiplier];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EffectKind::Shield => "Shield",
+            EffectKind::Magnet => "Magnet",
+            EffectKind::SlowMotion => "Slow Motion",
+            EffectKind::ScoreMultiplier => "Score Multiplier",
+        }
+    }
+}
+
+// What picking up a power-up does while the same effect is still running.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Stacking {
+    // Starts the timer over.
+    #[default]
+    Refresh,
+    // Adds the duration to what is left, up to `max` seconds.
+    Extend { max: f64 },
+    // Adds a stack, up to `max`, and starts the timer over.
+    Stack { max: u32 },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EffectDef {
+    pub kind: EffectKind,
+    // Seconds of simulation time.
+    pub duration: f64,
+    // Unused by shields.
+    #[serde(default)]
+    pub strength: f32,
+    #[serde(default)]
+    pub stacking: Stacking,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ActiveEffect {
+    pub kind: EffectKind,
+    pub strength: f32,
+    pub stacks: u32,
+    pub remaining: f64,
+    // What the timer started at, for the countdown ring.
+    pub duration: f64,
+}
+
+impl ActiveEffect {
+    pub fn fraction_left(&self) -> f32 {
+        (self.remaining / self.duration).clamp(0.0, 1.0) as f32
+    }
+}
+
+// The power-ups running on the player, at most one entry per kind.
+#[derive(Clone, Debug)]
+pub struct Effects {
+    active: Vec<ActiveEffect>,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self { active: Vec::with_capacity(EffectKind::ALL.len()) }
+    }
+}
+
+impl Effects {
+    pub fn apply(&mut self, def: &EffectDef) {
+        let effect = match self.active.iter_mut().find(|effect| effect.kind == def.kind) {
+            Some(effect) => effect,
+            None => {
+                self.active.push(ActiveEffect { kind: def.kind, strength: def.strength, stacks: 1, remaining: def.duration, duration: def.duration });
+                return;
+            },
+        };
+        match def.stacking {
+            Stacking::Refresh => {
+                effect.remaining = def.duration;
+                effect.duration = def.duration;
+            },
+            Stacking::Extend { max } => {
+                effect.remaining = (effect.remaining + def.duration).min(max);
+                effect.duration = effect.remaining;
+            },
+            Stacking::Stack { max } => {
+                effect.stacks = (effect.stacks + 1).min(max);
+                effect.remaining = def.duration;
+                effect.duration = def.duration;
+            },
+        }
+    }
+
+    pub fn tick(&mut self, dt: f64) {
+        for effect in self.active.iter_mut() {
+            effect.remaining -= dt;
+        }
+        self.active.retain(|effect| effect.remaining > 0.0 && effect.stacks > 0);
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ActiveEffect> {
+        self.active.iter()
+    }
+
+    fn get(&self, kind: EffectKind) -> Option<&ActiveEffect> {
+        self.active.iter().find(|effect| effect.kind == kind)
+    }
+
+    // Uses up one shield stack, if there is one.
+    pub fn absorb_hit(&mut self) -> bool {
+        match self.active.iter_mut().find(|effect| effect.kind == EffectKind::Shield) {
+            Some(shield) => {
+                shield.stacks -= 1;
+                self.active.retain(|effect| effect.stacks > 0);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn magnet_radius(&self) -> f32 {
+        self.get(EffectKind::Magnet).map_or(0.0, |magnet| magnet.strength * magnet.stacks as f32)
+    }
+
+    pub fn fall_speed_factor(&self) -> f32 {
+        self.get(EffectKind::SlowMotion).map_or(1.0, |slow| slow.strength.powi(slow.stacks as i32))
+    }
+
+    pub fn score_multiplier(&self) -> f32 {
+        self.get(EffectKind::ScoreMultiplier).map_or(1.0, |bonus| 1.0 + (bonus.strength - 1.0) * bonus.stacks as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(kind: EffectKind, strength: f32, stacking: Stacking) -> EffectDef {
+        EffectDef { kind, duration: 10.0, strength, stacking }
+    }
+
+    fn remaining(effects: &Effects, kind: EffectKind) -> Option<f64> {
+        effects.get(kind).map(|effect| effect.remaining)
+    }
+
+    #[test]
+    fn refresh_starts_the_timer_over() {
+        let mut effects = Effects::default();
+        let slow = def(EffectKind::SlowMotion, 0.5, Stacking::Refresh);
+        effects.apply(&slow);
+        effects.tick(4.0);
+        assert_eq!(remaining(&effects, EffectKind::SlowMotion), Some(6.0));
+        effects.apply(&slow);
+        assert_eq!(remaining(&effects, EffectKind::SlowMotion), Some(10.0));
+        assert_eq!(effects.fall_speed_factor(), 0.5);
+    }
+
+    #[test]
+    fn extend_adds_time_up_to_max() {
+        let mut effects = Effects::default();
+        let magnet = def(EffectKind::Magnet, 100.0, Stacking::Extend { max: 25.0 });
+        effects.apply(&magnet);
+        effects.tick(2.0);
+        effects.apply(&magnet);
+        assert_eq!(remaining(&effects, EffectKind::Magnet), Some(18.0));
+        effects.apply(&magnet);
+        assert_eq!(remaining(&effects, EffectKind::Magnet), Some(25.0));
+        assert_eq!(effects.magnet_radius(), 100.0);
+    }
+
+    #[test]
+    fn stack_adds_up_to_max() {
+        let mut effects = Effects::default();
+        let bonus = def(EffectKind::ScoreMultiplier, 2.0, Stacking::Stack { max: 3 });
+        for _ in 0..5 {
+            effects.apply(&bonus);
+        }
+        assert_eq!(effects.get(EffectKind::ScoreMultiplier).unwrap().stacks, 3);
+        assert_eq!(effects.score_multiplier(), 4.0);
+        let slow = def(EffectKind::SlowMotion, 0.5, Stacking::Stack { max: 2 });
+        effects.apply(&slow);
+        effects.apply(&slow);
+        assert_eq!(effects.fall_speed_factor(), 0.25);
+    }
+
+    #[test]
+    fn effects_expire() {
+        let mut effects = Effects::default();
+        effects.apply(&def(EffectKind::Magnet, 100.0, Stacking::Refresh));
+        effects.apply(&EffectDef { kind: EffectKind::ScoreMultiplier, duration: 20.0, strength: 2.0, stacking: Stacking::Refresh });
+        effects.tick(10.0);
+        assert_eq!(remaining(&effects, EffectKind::Magnet), None);
+        assert_eq!(effects.magnet_radius(), 0.0);
+        assert_eq!(effects.score_multiplier(), 2.0);
+        effects.tick(10.0);
+        assert_eq!(effects.iter().count(), 0);
+        assert_eq!(effects.score_multiplier(), 1.0);
+    }
+
+    #[test]
+    fn shield_stacks_absorb_one_hit_each() {
+        let mut effects = Effects::default();
+        let shield = def(EffectKind::Shield, 0.0, Stacking::Stack { max: 2 });
+        effects.apply(&shield);
+        effects.apply(&shield);
+        assert!(effects.absorb_hit());
+        assert!(effects.absorb_hit());
+        assert!(!effects.absorb_hit());
+        assert_eq!(effects.iter().count(), 0);
+    }
+}
@@ -0,0 +1,130 @@
+use macroquad::{prelude::*, rand::gen_range};
+
+// How much trauma and flash fade per second.
+const SHAKE_DECAY: f32 = 2.0;
+const FLASH_DECAY: f32 = 3.0;
+// Pixels the screen moves at full trauma.
+const MAX_SHAKE: f32 = 14.0;
+const VIGNETTE_BANDS: usize = 8;
+
+// Controller rumble, for platforms that can do it. Macroquad has no rumble
+// support, so the game ships with `NoRumble`.
+pub trait Rumble {
+    fn rumble(&mut self, strength: f32, seconds: f32);
+}
+
+pub struct NoRumble;
+
+impl Rumble for NoRumble {
+    fn rumble(&mut self, _strength: f32, _seconds: f32) {}
+}
+
+// Screen shake, a red flash around the edges and rumble when the ship is hit.
+// Purely cosmetic, so it runs on frame time rather than simulation ticks.
+pub struct HitFeedback {
+    trauma: f32,
+    flash: f32,
+    rumble: Box<dyn Rumble>,
+}
+
+impl HitFeedback {
+    pub fn new(rumble: Box<dyn Rumble>) -> Self {
+        Self { trauma: 0.0, flash: 0.0, rumble }
+    }
+
+    // `strength` is between 0 and 1.
+    pub fn hit(&mut self, strength: f32, rumble: bool) {
+        self.trauma = (self.trauma + strength).min(1.0);
+        self.flash = self.flash.max(strength);
+        if rumble {
+            self.rumble.rumble(strength, 0.25);
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.trauma = (self.trauma - SHAKE_DECAY * dt).max(0.0);
+        self.flash = (self.flash - FLASH_DECAY * dt).max(0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.trauma = 0.0;
+        self.flash = 0.0;
+    }
+
+    // Shake grows with the square of the trauma so light hits stay subtle.
+    pub fn shake_offset(&self) -> Vec2 {
+        let amount = MAX_SHAKE * self.trauma * self.trauma;
+        if amount <= 0.0 {
+            return Vec2::ZERO;
+        }
+        vec2(gen_range(-amount, amount), gen_range(-amount, amount))
+    }
+
+    pub fn draw_vignette(&self) {
+        if self.flash <= 0.0 {
+            return;
+        }
+        let (width, height) = (screen_width(), screen_height());
+        let band = width.min(height) * 0.15 / VIGNETTE_BANDS as f32;
+        for index in 0..VIGNETTE_BANDS {
+            let color = Color::new(0.9, 0.05, 0.05, 0.5 * self.flash * (1.0 - index as f32 / VIGNETTE_BANDS as f32));
+            let inset = band * index as f32;
+            draw_rectangle(inset, inset, width - inset * 2.0, band, color);
+            draw_rectangle(inset, height - inset - band, width - inset * 2.0, band, color);
+            draw_rectangle(inset, inset + band, band, height - inset * 2.0 - band * 2.0, color);
+            draw_rectangle(width - inset - band, inset + band, band, height - inset * 2.0 - band * 2.0, color);
+        }
+    }
+}
+
+const POPUP_LIFETIME: f32 = 0.8;
+// Pixels per second popups drift upwards.
+const POPUP_RISE: f32 = 60.0;
+const MAX_POPUPS: usize = 16;
+
+struct Popup {
+    text: String,
+    position: Vec2,
+    color: Color,
+    age: f32,
+}
+
+// Short-lived text floating up from where something happened, like the points for a pickup.
+pub struct Popups {
+    items: Vec<Popup>,
+}
+
+impl Popups {
+    pub fn new() -> Self {
+        Self { items: Vec::with_capacity(MAX_POPUPS) }
+    }
+
+    // The oldest popup makes room when there are too many on screen.
+    pub fn add(&mut self, text: String, position: Vec2, color: Color) {
+        if self.items.len() >= MAX_POPUPS {
+            self.items.remove(0);
+        }
+        self.items.push(Popup { text, position, color, age: 0.0 });
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for popup in self.items.iter_mut() {
+            popup.age += dt;
+        }
+        self.items.retain(|popup| popup.age < POPUP_LIFETIME);
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    // With `rise` off they fade out in place.
+    pub fn draw(&self, font_size: f32, rise: bool) {
+        for popup in self.items.iter() {
+            let fade = 1.0 - popup.age / POPUP_LIFETIME;
+            let y = if rise { popup.position.y - popup.age * POPUP_RISE } else { popup.position.y };
+            let color = Color::new(popup.color.r, popup.color.g, popup.color.b, popup.color.a * fade);
+            draw_text(&popup.text, popup.position.x, y, font_size, color);
+        }
+    }
+}
@@ -1,12 +1,21 @@
-use macroquad::{prelude::{Texture2D, load_texture}, audio::{Sound, load_sound}};
+use macroquad::{prelude::{Image, Texture2D, load_texture, load_image, load_file, load_string}, audio::{Sound, load_sound, load_sound_from_bytes}};
+use crate::{audio::{Track, wav_duration}, content::Content, levels::LevelSet};
+
+const FALLBACK_TRACK_LENGTH: f64 = 60.0;
+
+// Textures and sounds loaded before the game starts. Object textures and sounds
+// line up with the content's archetypes, skin textures with its skins.
+pub struct Assets {
+    pub player_texture: Texture2D,
+    pub skin_textures: Vec<Texture2D>,
+    pub object_textures: Vec<Texture2D>,
+    pub object_sounds: Vec<Option<Sound>>,
+    pub game_music: Vec<Track>,
+    pub game_sounds: Vec<Sound>,
+}
 
 pub trait Game {
-    fn new(
-        player_texture: Texture2D, 
-        space_junk_textures: Vec<Texture2D>,
-        game_music: Vec<Sound>,
-        game_sounds: Vec<Sound>
-    ) -> Self;
+    fn new(content: Content, levels: LevelSet, assets: Assets) -> Self;
     fn update(&mut self);
     fn draw(&mut self);
     
@@ -33,6 +42,16 @@ pub async fn init_texture(path:  &str) -> Texture2D {
     texture
 }
 
+pub async fn init_image(path:  &str) -> Image {
+    match load_image(path).await {
+        Ok(image) => image,
+        Err(error) => {
+            println!("{error}");
+            exit_game();
+        },
+    }
+}
+
 pub async fn init_sound(path:  &str) -> Sound {
     let sound_result = load_sound(path).await;
     let sound: Sound;
@@ -45,3 +64,63 @@ pub async fn init_sound(path:  &str) -> Sound {
     }
     sound
 }
+
+
+pub async fn init_track(path: &str) -> Track {
+    let bytes = match load_file(path).await {
+        Ok(data) => data,
+        Err(error) => {
+            println!("{error}");
+            exit_game();
+        },
+    };
+    let sound = match load_sound_from_bytes(&bytes).await {
+        Ok(sound_loaded) => sound_loaded,
+        Err(error) => {
+            println!("{error}");
+            exit_game();
+        },
+    };
+    let length = match wav_duration(&bytes) {
+        Some(length) => length,
+        None => {
+            println!("Could not read the length of {}, assuming {} seconds", path, FALLBACK_TRACK_LENGTH);
+            FALLBACK_TRACK_LENGTH
+        },
+    };
+    Track { sound, length }
+}
+
+pub async fn init_content(path: &str) -> Content {
+    let contents = match load_string(path).await {
+        Ok(data) => data,
+        Err(error) => {
+            println!("{error}");
+            exit_game();
+        },
+    };
+    match Content::parse(&contents) {
+        Ok(content) => content,
+        Err(error) => {
+            println!("{error}");
+            exit_game();
+        },
+    }
+}
+
+pub async fn init_levels(path: &str, content: &Content) -> LevelSet {
+    let contents = match load_string(path).await {
+        Ok(data) => data,
+        Err(error) => {
+            println!("{error}");
+            exit_game();
+        },
+    };
+    match LevelSet::parse(&contents, content) {
+        Ok(levels) => levels,
+        Err(error) => {
+            println!("{error}");
+            exit_game();
+        },
+    }
+}
\ No newline at end of file
@@ -0,0 +1,127 @@
+use chrono::{Datelike, Local};
+use serde::{Deserialize, Serialize};
+use crate::{replay::RunMode, simulation::RunStats};
+
+pub const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScoreEntry {
+    pub name: String,
+    pub score: i32,
+    // Local date the run was played, as YYYY-MM-DD.
+    pub date: String,
+    pub seed: u64,
+    pub mode: RunMode,
+    // Seconds of play.
+    pub duration: f64,
+    pub scraps: u32,
+    pub asteroids_hit: u32,
+    // Longest run of pickups. Entries from before combos existed have none.
+    #[serde(default)]
+    pub best_combo: u32,
+}
+
+impl ScoreEntry {
+    pub fn mode_label(&self) -> &str {
+        match &self.mode {
+            RunMode::Endless(preset) => preset.label(),
+            RunMode::Level(name) => name,
+        }
+    }
+}
+
+// Totals over every run ever finished, replays not included.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct LifetimeStats {
+    pub runs: u32,
+    pub playtime: f64,
+    pub points: i64,
+    pub scraps: u64,
+    pub asteroids_hit: u64,
+    #[serde(default)]
+    pub best_combo: u32,
+}
+
+impl LifetimeStats {
+    pub fn add_run(&mut self, stats: &RunStats, points: i32, duration: f64) {
+        self.runs += 1;
+        self.playtime += duration;
+        self.points += points as i64;
+        self.scraps += stats.scraps as u64;
+        self.asteroids_hit += stats.asteroids_hit as u64;
+        self.best_combo = self.best_combo.max(stats.best_combo);
+    }
+}
+
+pub fn today() -> String {
+    let today = Local::now().date_naive();
+    format!("{:04}-{:02}-{:02}", today.year(), today.month(), today.day())
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    }
+    else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+// Whether a score would make it onto a table that is kept sorted best first.
+pub fn qualifies(entries: &[ScoreEntry], score: i32) -> bool {
+    score > 0 && (entries.len() < LEADERBOARD_SIZE || entries.last().is_some_and(|last| score > last.score))
+}
+
+// Adds the entry below any equal scores and returns its rank, or `None` if it didn't make the table.
+pub fn insert(entries: &mut Vec<ScoreEntry>, entry: ScoreEntry) -> Option<usize> {
+    if !qualifies(entries, entry.score) {
+        return None;
+    }
+    let rank = entries.iter().position(|other| entry.score > other.score).unwrap_or(entries.len());
+    entries.insert(rank, entry);
+    entries.truncate(LEADERBOARD_SIZE);
+    Some(rank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::difficulty::Preset;
+
+    fn entry(name: &str, score: i32) -> ScoreEntry {
+        ScoreEntry { name: name.to_string(), score, date: String::from("2026-10-18"), seed: 1, mode: RunMode::Endless(Preset::Normal), duration: 60.0, scraps: 0, asteroids_hit: 0, best_combo: 0 }
+    }
+
+    fn scores(entries: &[ScoreEntry]) -> Vec<i32> {
+        entries.iter().map(|entry| entry.score).collect()
+    }
+
+    #[test]
+    fn insert_keeps_best_first() {
+        let mut entries = Vec::new();
+        assert_eq!(insert(&mut entries, entry("a", 50)), Some(0));
+        assert_eq!(insert(&mut entries, entry("b", 80)), Some(0));
+        assert_eq!(insert(&mut entries, entry("c", 60)), Some(1));
+        assert_eq!(insert(&mut entries, entry("d", 10)), Some(3));
+        assert_eq!(scores(&entries), vec![80, 60, 50, 10]);
+    }
+
+    #[test]
+    fn equal_scores_go_below_earlier_ones() {
+        let mut entries = vec![entry("first", 50)];
+        assert_eq!(insert(&mut entries, entry("second", 50)), Some(1));
+        assert_eq!(entries[0].name, "first");
+    }
+
+    #[test]
+    fn full_table_drops_the_lowest() {
+        let mut entries: Vec<ScoreEntry> = (1..=LEADERBOARD_SIZE as i32).rev().map(|score| entry("old", score * 10)).collect();
+        // Ties with the last place and zero scores don't make it.
+        assert_eq!(insert(&mut entries, entry("tie", 10)), None);
+        assert_eq!(insert(&mut entries, entry("zero", 0)), None);
+        assert_eq!(insert(&mut entries, entry("new", 55)), Some(5));
+        assert_eq!(entries.len(), LEADERBOARD_SIZE);
+        assert_eq!(scores(&entries), vec![100, 90, 80, 70, 60, 55, 50, 40, 30, 20]);
+    }
+}
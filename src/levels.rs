@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use crate::{content::{Content, ContentError}, difficulty::{Curve, Difficulty}};
+
+pub const LEVELS_PATH: &str = "res/levels.json";
+
+fn default_player_speed() -> f32 {
+    400.0
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Goal {
+    // Reach the target before the script runs out of objects.
+    Score { target: i32 },
+    // Stay alive until the time is up.
+    Survive { seconds: f64 },
+}
+
+// Drops one `archetype` at every `x` (a fraction of the screen width) `at` seconds
+// into the level, then again `repeat` more times, `every` seconds apart.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LevelEvent {
+    pub at: f64,
+    pub archetype: String,
+    pub x: Vec<f32>,
+    #[serde(default)]
+    pub repeat: u32,
+    #[serde(default)]
+    pub every: f64,
+    #[serde(skip)]
+    pub archetype_index: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Level {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // Pixels per second, before each archetype's own multiplier.
+    pub fall_speed: f32,
+    #[serde(default = "default_player_speed")]
+    pub player_speed: f32,
+    pub goal: Goal,
+    // Completing a level is worth one star. These are the bars for the second and
+    // third: points scored on survival levels, seconds taken on score levels.
+    pub stars: [f64; 2],
+    pub events: Vec<LevelEvent>,
+}
+
+impl Level {
+    // Levels spawn from their script, so only the speeds matter.
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty {
+            player_speed: Curve::Constant { value: self.player_speed },
+            fall_speed: Curve::Constant { value: self.fall_speed },
+            spawn_density: Curve::Constant { value: 0.0 },
+            asteroid_ratio: Curve::Constant { value: 0.0 },
+        }
+    }
+
+    pub fn rating(&self, points: i32, time: f64) -> u8 {
+        let beaten = |bar: f64| match self.goal {
+            Goal::Score { .. } => time <= bar,
+            Goal::Survive { .. } => points as f64 >= bar,
+        };
+        1 + self.stars.iter().filter(|bar| beaten(**bar)).count() as u8
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LevelSet {
+    pub levels: Vec<Level>,
+}
+
+impl LevelSet {
+    pub fn parse(contents: &str, content: &Content) -> Result<LevelSet, ContentError> {
+        let mut set: LevelSet = serde_json::from_str(contents).map_err(ContentError::Parse)?;
+        for (index, level) in set.levels.iter_mut().enumerate() {
+            let invalid = |name: &str, reason: &str| Err(ContentError::Invalid(format!("level '{}': {}", name, reason)));
+            if level.name.is_empty() {
+                return Err(ContentError::Invalid(format!("level #{} has no name", index)));
+            }
+            if !level.fall_speed.is_finite() || level.fall_speed <= 0.0 || !level.player_speed.is_finite() || level.player_speed <= 0.0 {
+                return invalid(&level.name, "speeds must be positive");
+            }
+            let ordered = match level.goal {
+                Goal::Score { target } if target <= 0 => return invalid(&level.name, "score target must be positive"),
+                Goal::Survive { seconds } if seconds <= 0.0 => return invalid(&level.name, "survival time must be positive"),
+                Goal::Score { .. } => level.stars[0] >= level.stars[1],
+                Goal::Survive { .. } => level.stars[0] <= level.stars[1],
+            };
+            if !ordered {
+                return invalid(&level.name, "the third star must be harder to get than the second");
+            }
+            for event in level.events.iter_mut() {
+                event.archetype_index = match content.archetypes.iter().position(|archetype| archetype.name == event.archetype) {
+                    Some(archetype_index) => archetype_index,
+                    None => return invalid(&level.name, &format!("unknown archetype '{}'", event.archetype)),
+                };
+                if event.at < 0.0 || event.every < 0.0 || event.x.iter().any(|x| !(0.0..=1.0).contains(x)) {
+                    return invalid(&level.name, "events need non-negative times and x between 0 and 1");
+                }
+            }
+        }
+        for (index, level) in set.levels.iter().enumerate() {
+            if set.levels[..index].iter().any(|other| other.name == level.name) {
+                return Err(ContentError::Invalid(format!("level '{}' is defined more than once", level.name)));
+            }
+        }
+        Ok(set)
+    }
+
+    // The first level is always open, every other one once the level before it is beaten.
+    pub fn is_unlocked(&self, index: usize, records: &LevelRecords) -> bool {
+        index == 0 || self.levels.get(index - 1).is_some_and(|previous| records.contains_key(&previous.name))
+    }
+}
+
+// The best completed run of a level, stored in the save file by level name.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct LevelRecord {
+    pub stars: u8,
+    pub score: i32,
+    pub time: f64,
+}
+
+impl LevelRecord {
+    pub fn is_better_than(&self, other: &LevelRecord) -> bool {
+        (self.stars, self.score) > (other.stars, other.score)
+    }
+}
+
+pub type LevelRecords = std::collections::BTreeMap<String, LevelRecord>;
+
+// One spawn from a level's script, with `x` as a fraction of the screen width.
+#[derive(Clone, Copy, Debug)]
+pub struct ScriptedSpawn {
+    pub at: f64,
+    pub archetype: usize,
+    pub x: f32,
+}
+
+// A level being played: its script flattened into time order, and how it ended.
+#[derive(Clone, Debug)]
+pub struct LevelRun {
+    pub index: usize,
+    pub level: Level,
+    timeline: Vec<ScriptedSpawn>,
+    next: usize,
+    // Stars earned, set once the goal is met.
+    pub result: Option<u8>,
+}
+
+impl LevelRun {
+    pub fn new(index: usize, level: &Level) -> Self {
+        let mut timeline = Vec::new();
+        for event in level.events.iter() {
+            for round in 0..=event.repeat {
+                for x in event.x.iter() {
+                    timeline.push(ScriptedSpawn { at: event.at + event.every * round as f64, archetype: event.archetype_index, x: *x });
+                }
+            }
+        }
+        timeline.sort_by(|a, b| a.at.total_cmp(&b.at));
+        Self { index, level: level.clone(), timeline, next: 0, result: None }
+    }
+
+    pub fn reset(&mut self) {
+        self.next = 0;
+        self.result = None;
+    }
+
+    pub fn next_spawn(&mut self, time: f64) -> Option<ScriptedSpawn> {
+        let spawn = *self.timeline.get(self.next)?;
+        if spawn.at > time {
+            return None;
+        }
+        self.next += 1;
+        Some(spawn)
+    }
+
+    pub fn script_done(&self) -> bool {
+        self.next >= self.timeline.len()
+    }
+
+    pub fn is_won(&self, points: i32, time: f64) -> bool {
+        match self.level.goal {
+            Goal::Score { target } => points >= target,
+            Goal::Survive { seconds } => time >= seconds,
+        }
+    }
+
+    // Score levels are lost once the script has run out and everything has fallen
+    // past short of the target. Survival levels only end early with the ship.
+    pub fn is_lost(&self, cleared: bool) -> bool {
+        matches!(self.level.goal, Goal::Score { .. }) && self.script_done() && cleared
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::test_content;
+
+    const ARCHETYPES: &str = r#"[
+        { "name": "scrap", "category": "scrap", "texture": "scrap.png", "size": [40, 40] },
+        { "name": "rock", "category": "asteroid", "texture": "rock.png", "size": [60, 60] }
+    ]"#;
+    const LEVELS: &str = r#"{ "levels": [
+        { "name": "Collect", "fall_speed": 100, "goal": { "type": "score", "target": 50 }, "stars": [30, 20], "events": [
+            { "at": 2, "archetype": "rock", "x": [0.5] },
+            { "at": 0, "archetype": "scrap", "x": [0.25, 0.75], "repeat": 2, "every": 1.5 }
+        ] },
+        { "name": "Hold On", "fall_speed": 100, "goal": { "type": "survive", "seconds": 45 }, "stars": [100, 200], "events": [
+            { "at": 1, "archetype": "rock", "x": [0.5] }
+        ] },
+        { "name": "Last", "fall_speed": 100, "goal": { "type": "survive", "seconds": 10 }, "stars": [0, 0], "events": [] }
+    ] }"#;
+
+    fn levels() -> LevelSet {
+        LevelSet::parse(LEVELS, &test_content(ARCHETYPES)).unwrap()
+    }
+
+    #[test]
+    fn rating_counts_the_bars_beaten() {
+        let levels = levels();
+        // Score levels are rated on time, survival levels on points.
+        assert_eq!(levels.levels[0].rating(0, 40.0), 1);
+        assert_eq!(levels.levels[0].rating(0, 30.0), 2);
+        assert_eq!(levels.levels[0].rating(0, 12.5), 3);
+        assert_eq!(levels.levels[1].rating(99, 45.0), 1);
+        assert_eq!(levels.levels[1].rating(150, 45.0), 2);
+        assert_eq!(levels.levels[1].rating(200, 45.0), 3);
+    }
+
+    #[test]
+    fn levels_unlock_in_order() {
+        let levels = levels();
+        let mut records = LevelRecords::new();
+        assert!(levels.is_unlocked(0, &records));
+        assert!(!levels.is_unlocked(1, &records));
+        records.insert("Collect".to_string(), LevelRecord { stars: 1, score: 50, time: 40.0 });
+        assert!(levels.is_unlocked(1, &records));
+        assert!(!levels.is_unlocked(2, &records));
+        assert!(!levels.is_unlocked(3, &records));
+    }
+
+    #[test]
+    fn more_stars_beat_a_higher_score() {
+        let record = LevelRecord { stars: 2, score: 100, time: 30.0 };
+        assert!(LevelRecord { stars: 3, score: 10, time: 60.0 }.is_better_than(&record));
+        assert!(LevelRecord { stars: 2, score: 101, time: 60.0 }.is_better_than(&record));
+        assert!(!LevelRecord { stars: 2, score: 100, time: 10.0 }.is_better_than(&record));
+        assert!(!LevelRecord { stars: 1, score: 500, time: 10.0 }.is_better_than(&record));
+    }
+
+    #[test]
+    fn timeline_plays_the_script_in_time_order() {
+        let levels = levels();
+        let mut run = LevelRun::new(0, &levels.levels[0]);
+        let mut spawns = Vec::new();
+        for time in [0.0, 1.5, 2.0, 3.0] {
+            while let Some(spawn) = run.next_spawn(time) {
+                spawns.push((spawn.at, spawn.archetype, spawn.x));
+            }
+        }
+        assert_eq!(spawns, vec![(0.0, 0, 0.25), (0.0, 0, 0.75), (1.5, 0, 0.25), (1.5, 0, 0.75), (2.0, 1, 0.5), (3.0, 0, 0.25), (3.0, 0, 0.75)]);
+        assert!(run.script_done());
+        run.reset();
+        assert!(!run.script_done());
+        assert_eq!(run.next_spawn(0.0).map(|spawn| spawn.x), Some(0.25));
+    }
+
+    #[test]
+    fn only_score_levels_are_lost_when_the_script_runs_out() {
+        let levels = levels();
+        let mut score = LevelRun::new(0, &levels.levels[0]);
+        while score.next_spawn(10.0).is_some() {}
+        assert!(!score.is_lost(false));
+        assert!(score.is_lost(true));
+        assert!(!score.is_won(49, 10.0));
+        assert!(score.is_won(50, 10.0));
+
+        let mut survive = LevelRun::new(1, &levels.levels[1]);
+        while survive.next_spawn(10.0).is_some() {}
+        assert!(survive.script_done());
+        assert!(!survive.is_lost(true));
+        assert!(!survive.is_won(1000, 44.9));
+        assert!(survive.is_won(0, 45.0));
+    }
+}
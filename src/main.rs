@@ -1,17 +1,51 @@
+mod audio;
+mod autosave;
+mod collision;
+mod combo;
+mod content;
+mod difficulty;
+mod director;
+mod effects;
+mod feedback;
 mod game;
+mod leaderboard;
+mod levels;
 mod object;
+mod particles;
+mod paths;
 mod player;
+mod replay;
+mod save;
+mod settings;
+mod shop;
+mod simulation;
 mod space_objects;
 mod timer;
+mod weapon;
 
-use macroquad::{prelude::*, rand::*, audio::{Sound, PlaySoundParams, play_sound, stop_sound}};
+use macroquad::{prelude::*, audio::Sound};
 use egui_macroquad::egui::{self, Pos2};
+use audio::{AudioManager, Playlist, Sfx};
+use autosave::Autosave;
+use difficulty::Preset;
+use feedback::{HitFeedback, NoRumble, Popups};
+use particles::{Emitter, Particles, DEBRIS, EXHAUST, SPARKLE, exhaust_direction};
+use content::{Content, CONTENT_PATH, alpha_threshold};
+use collision::alpha_hull;
+use paths::{Paths, resource, replay_arg};
+use replay::{Replay, ReplayPlayer, RunMode};
+use timer::{Clock, FixedTimestep, PausableClock, SystemClock};
 use player::Player;
-use space_objects::SpaceObject;
-use timer::Timer;
-use game::{Game, exit_game, init_texture, init_sound};
+use save::SaveData;
+use settings::{Settings, Action, LANGUAGES, UI_SCALE_RANGE, WINDOW_HEIGHT_RANGE, WINDOW_WIDTH_RANGE};
+use shop::{Upgrade, buy_skin, selected_skin, skin_for_sale, skin_owned};
+use simulation::{Simulation, InputSnapshot, SimEvent, random_seed, daily_seed};
+use game::{Assets, Game, exit_game, init_texture, init_image, init_sound, init_track, init_content, init_levels};
+use levels::{LevelSet, LevelRecord, LEVELS_PATH};
+use leaderboard::{ScoreEntry, format_duration, today};
 use object::Object;
 use macroui::button::Button;
+use std::{collections::HashMap, path::Path, rc::Rc};
 
 #[derive(Clone, Copy)]
 enum State {
@@ -21,30 +55,51 @@ enum State {
     Game,
     Options,
     Shop,
+    LevelSelect,
+    Replay,
+    Leaderboard,
     GameOver,
 }
 
-fn calculate_speed(points: i32) -> f32 {
-    let mut speed: f32 = (points as f32) * 2.0 + 250.0;
-    if speed > 3500.0 {
-        speed = 3500.0;
-    }
-    speed
-}
+const MAX_CATCH_UP_TICKS: u32 = 8;
+const TRAIL_LENGTH: usize = 12;
 
 struct GameStruct {
-    player: Player,
-    scraps: Vec<SpaceObject>,
-    asteroids: Vec<SpaceObject>,
+    paths: Paths,
+    simulation: Simulation,
+    levels: LevelSet,
+    object_textures: Vec<Texture2D>,
+    object_sounds: Vec<Option<Sound>>,
+    // One per skin in the content file, in the same order.
+    skin_textures: Vec<Texture2D>,
+    // The skin hovered in the shop, shown instead of the equipped one.
+    skin_preview: Option<usize>,
+    // Recent exhaust positions behind the ship, oldest first. Only drawn, never simulated.
+    trail: Vec<Vec2>,
+    feedback: HitFeedback,
+    popups: Popups,
+    particles: Particles,
+    exhaust: Emitter,
+    save_data: SaveData,
+    autosave: Autosave,
     debug: bool,
     paused: bool,
     state: State,
     previous_state: State,
-    game_music: Vec<Sound>,
-    game_sounds: Vec<Sound>,
-    music_timer: Timer,
-    music_volume: f32,
-    sound_volume: f32
+    audio: AudioManager,
+    game_clock: PausableClock<SystemClock>,
+    settings: Settings,
+    rebinding: Option<Action>,
+    seed_input: String,
+    // The run being played, recorded as it goes.
+    recording: Option<Replay>,
+    // The run being watched in the Replay state.
+    replay: Option<ReplayPlayer>,
+    replay_message: Option<String>,
+    // A finished run that made the leaderboard, waiting for a name.
+    pending_score: Option<ScoreEntry>,
+    name_input: String,
+    timestep: FixedTimestep
 }
 
 impl GameStruct {
@@ -60,7 +115,7 @@ impl GameStruct {
                 .default_pos(Pos2{ x: (screen_width() / 2.0 + 250.0) / 3.0, y: (screen_height() / 2.0 + 200.0) / 3.0 })
                 .resizable(false)
                 .show(egui_ctx, |ui| {
-                    egui_ctx.set_pixels_per_point(3.0);
+                    egui_ctx.set_pixels_per_point(3.0 * self.settings.ui_scale);
                     ui.label("Paused");
                     if ui.button("Continue").clicked() {
                         self.paused = false;
@@ -107,9 +162,128 @@ impl GameStruct {
         continue_btn.draw();
         if continue_btn.clicked() {
             self.paused = false;
+            self.game_clock.resume();
+            self.simulation.set_paused(false);
+        }
+        let shop_btn_rect = Rect {
+            x: label_x,
+            y: btn_y * 4.0 + btn_height + 10.0,
+            w: btn_width,
+            h: btn_height
+        };
+        let mut shop_btn = Button::new(
+            shop_btn_rect,
+            String::from("Shop"),
+            25.0,
+            LIGHTGRAY,
+            BLACK
+        );
+        shop_btn.draw();
+        if shop_btn.clicked() {
+            self.previous_state = State::Game;
+            self.set_state(State::Shop);
+        }
+    }
+
+    // Whether the run on screen is paused, so frame-time effects should hold still.
+    fn frozen(&self) -> bool {
+        self.paused || self.replay.as_ref().is_some_and(|player| player.paused)
+    }
+
+    fn skin_trail(&self, index: usize) -> Option<Color> {
+        let [r, g, b] = self.simulation.content.skins.get(index)?.trail?;
+        Some(Color::from_rgba(r, g, b, 255))
+    }
+
+    fn draw_trail(&self, points: &[Vec2], color: Color) {
+        for (age, point) in points.iter().rev().enumerate() {
+            let fade = 1.0 - age as f32 / TRAIL_LENGTH as f32;
+            draw_circle(point.x, point.y, 8.0 * fade, Color::new(color.r, color.g, color.b, 0.6 * fade));
         }
     }
 
+    // Scrolls the trail down with the falling objects and adds the ship's current exhaust position.
+    fn update_trail(&mut self, exhaust: Vec2) {
+        if self.frozen() {
+            return;
+        }
+        let scroll = self.simulation.difficulty.fall_speed.value(self.simulation.progress()) * get_frame_time();
+        for point in self.trail.iter_mut() {
+            point.y += scroll;
+        }
+        self.trail.push(exhaust);
+        if self.trail.len() > TRAIL_LENGTH {
+            self.trail.remove(0);
+        }
+    }
+
+    // One icon per running power-up, with a ring that empties as it runs out.
+    fn draw_effects(&self, x: f32, y: f32) {
+        const ICON_SIZE: f32 = 40.0;
+        const RING_SEGMENTS: usize = 32;
+        for (slot, effect) in self.simulation.player.effects.iter().enumerate() {
+            let center = vec2(x + ICON_SIZE / 2.0 + slot as f32 * (ICON_SIZE + 20.0), y + ICON_SIZE / 2.0);
+            let icon = self.simulation.content.archetypes.iter().position(|archetype| archetype.effect == Some(effect.kind));
+            match icon {
+                Some(index) => {
+                    let icon_parmas = DrawTextureParams{
+                        dest_size: Some(Vec2{ x: ICON_SIZE, y: ICON_SIZE}),
+                        ..Default::default()
+                    };
+                    draw_texture_ex(self.object_textures[index], center.x - ICON_SIZE / 2.0, center.y - ICON_SIZE / 2.0, WHITE, icon_parmas);
+                },
+                None => draw_circle(center.x, center.y, ICON_SIZE / 2.0, GRAY),
+            }
+            let radius = ICON_SIZE / 2.0 + 5.0;
+            let segments = (effect.fraction_left() * RING_SEGMENTS as f32).ceil() as usize;
+            for segment in 0..segments {
+                let angle = |step: usize| -std::f32::consts::FRAC_PI_2 + step as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                let (start, end) = (angle(segment), angle(segment + 1));
+                draw_line(center.x + radius * start.cos(), center.y + radius * start.sin(), center.x + radius * end.cos(), center.y + radius * end.sin(), 3.0, WHITE);
+            }
+            if effect.stacks > 1 {
+                draw_text(&format!("x{}", effect.stacks), center.x + ICON_SIZE / 2.0, center.y + ICON_SIZE / 2.0, 20.0, WHITE);
+            }
+        }
+    }
+
+    fn draw_replay_controls(&mut self) {
+        let window_frame = egui::containers::Frame{
+            fill: egui::Color32::from_black_alpha(160),
+            ..Default::default()
+        };
+        egui_macroquad::ui(|egui_ctx| {
+            egui::Window::new("Replay")
+                .title_bar(false)
+                .frame(window_frame)
+                .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -10.0))
+                .resizable(false)
+                .show(egui_ctx, |ui| {
+                    egui_ctx.set_pixels_per_point(3.0 * self.settings.ui_scale);
+                    let finished = self.simulation.game_over || self.replay.as_ref().is_none_or(|player| player.is_finished());
+                    if let Some(player) = self.replay.as_mut() {
+                        ui.label(format!("Replay  {} / {}{}", player.tick(), player.replay.ticks(), if finished { "  (finished)" } else { "" }));
+                        ui.horizontal(|ui| {
+                            if ui.button(if player.paused { "Play" } else { "Pause" }).clicked() {
+                                player.paused = !player.paused;
+                            }
+                            if ui.add_enabled(player.paused, egui::Button::new("Step")).clicked() {
+                                player.step = true;
+                            }
+                            for speed in [1, 2, 4, 8] {
+                                ui.selectable_value(&mut player.speed, speed, format!("{}x", speed));
+                            }
+                        });
+                    }
+                    if ui.button("Exit Replay").clicked() {
+                        self.set_state(State::MainMenu);
+                    }
+                });
+        });
+
+        egui_macroquad::draw();
+    }
+
     fn draw_main_menu(&mut self) {
         let window_frame = egui::containers::Frame{
             fill: egui::Color32::TRANSPARENT,
@@ -123,21 +297,61 @@ impl GameStruct {
                 .default_pos(Pos2{ x: (screen_width() / 2.0 + 250.0) / 3.0, y: (screen_height() / 2.0 + 200.0) / 3.0 })
                 .resizable(false)
                 .show(egui_ctx, |ui| {
-                    egui_ctx.set_pixels_per_point(3.0);
+                    egui_ctx.set_pixels_per_point(3.0 * self.settings.ui_scale);
                     ui.label("Space Cleanup");
+                    let difficulty = self.settings.difficulty;
+                    egui::ComboBox::from_label("Difficulty")
+                        .selected_text(difficulty.label())
+                        .show_ui(ui, |ui| {
+                            for preset in Preset::ALL {
+                                ui.selectable_value(&mut self.settings.difficulty, preset, preset.label());
+                            }
+                        });
+                    if self.settings.difficulty != difficulty {
+                        if let Err(error) = self.settings.save(&self.paths.settings_file()) {
+                            println!("Could not save settings: {}", error);
+                        }
+                    }
                     if ui.button("Play").clicked() {
-                        self.state = State::GameTutorial;
+                        self.simulation.start_endless(self.settings.difficulty, random_seed());
+                        self.set_state(State::GameTutorial);
+                    }
+                    // Always on Normal so everyone's daily runs can be compared.
+                    if ui.button("Daily Challenge").clicked() {
+                        self.simulation.start_endless(Preset::Normal, daily_seed());
+                        self.set_state(State::GameTutorial);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.add(egui::TextEdit::singleline(&mut self.seed_input).hint_text("Seed").desired_width(100.0));
+                        let seed = self.seed_input.trim().parse::<u64>().ok();
+                        if ui.add_enabled(seed.is_some(), egui::Button::new("Play Seed")).clicked() {
+                            if let Some(seed) = seed {
+                                self.simulation.start_endless(self.settings.difficulty, seed);
+                                self.set_state(State::GameTutorial);
+                            }
+                        }
+                    });
+                    if ui.button("Levels").clicked() {
+                        self.set_state(State::LevelSelect);
+                    }
+                    if ui.button("Shop").clicked() {
+                        self.previous_state = State::MainMenu;
+                        self.set_state(State::Shop);
+                    }
+                    if ui.button("Leaderboard").clicked() {
+                        self.previous_state = State::MainMenu;
+                        self.set_state(State::Leaderboard);
                     }
                     if ui.button("Credits").clicked() {
                         self.previous_state = State::MainMenu;
-                        self.state = State::Credits;
+                        self.set_state(State::Credits);
                     }
                     if ui.button("Options").clicked() {
                         self.previous_state = State::MainMenu;
-                        self.state = State::Options;
+                        self.set_state(State::Options);
                     }
                     if ui.button("Quit").clicked() {
-                        exit_game();
+                        self.quit();
                     }
                 });
         });
@@ -145,120 +359,425 @@ impl GameStruct {
         egui_macroquad::draw()
     }
 
-}
+    fn save_game(&mut self) {
+        self.simulation.player.write_save(&mut self.save_data);
+        match save::save(&self.paths.save_file(), &self.save_data) {
+            Ok(_) => self.autosave.mark_saved(&self.game_clock),
+            Err(error) => println!("Could not save: {}", error),
+        }
+    }
 
-impl Game for GameStruct {
-    fn new(
-        player_texture: Texture2D, 
-        space_object_textures: Vec<Texture2D>,
-        game_music: Vec<Sound>,
-        game_sounds: Vec<Sound>
-    ) -> Self 
-    {
-        let mut j = Vec::new();
-        let number_of_circles = gen_range(20, 30);
-        for _ in 0..number_of_circles {
-            let texture_chose = gen_range(0, 200);
-            let scrap_texture_number: i32;
-            if texture_chose > 50 {
-                scrap_texture_number = 0;
-            }
-            else if texture_chose < 50 && texture_chose > 100 {
-                scrap_texture_number = 0;
-            }
-            else {
-                scrap_texture_number = 0;
+    fn save_settings(&mut self) {
+        set_fullscreen(self.settings.fullscreen);
+        if !self.settings.fullscreen {
+            request_new_screen_size(self.settings.window_width as f32, self.settings.window_height as f32);
+        }
+        self.autosave.set_interval(self.settings.autosave_interval, &self.game_clock);
+        self.timestep.set_tick_rate(self.settings.tick_rate);
+        if let Err(error) = self.settings.save(&self.paths.settings_file()) {
+            println!("Could not save settings: {}", error);
+        }
+    }
+
+    fn set_state(&mut self, state: State) {
+        // A high score nobody typed a name for is still kept.
+        if matches!(self.state, State::GameOver) && !matches!(state, State::Leaderboard) {
+            self.submit_score();
+        }
+        if matches!(self.state, State::Game | State::Shop | State::GameOver) {
+            self.save_game();
+        }
+        if matches!(self.state, State::Options) {
+            self.rebinding = None;
+            self.save_settings();
+        }
+        if matches!(self.state, State::Replay) && !matches!(state, State::Replay) {
+            self.replay = None;
+            self.simulation.loadout = self.save_data.upgrades.loadout();
+            self.timestep.set_tick_rate(self.settings.tick_rate);
+            self.timestep.max_ticks = MAX_CATCH_UP_TICKS;
+        }
+        match state {
+            State::MainMenu | State::GameTutorial | State::Shop | State::LevelSelect => self.audio.set_playlist(Playlist::Menu, &SystemClock),
+            State::Game | State::Replay => {
+                self.timestep.reset();
+                self.audio.set_playlist(Playlist::Gameplay, &SystemClock);
+            },
+            State::GameOver => self.audio.set_playlist(Playlist::GameOver, &SystemClock),
+            State::Credits | State::Options | State::Leaderboard => {},
+        }
+        self.state = state;
+    }
+
+    // Starts playing whatever the simulation was just set up for, recording it from the first tick.
+    fn begin_run(&mut self) {
+        self.recording = Some(Replay::new(self.simulation.seed, self.run_mode(), self.settings.tick_rate, self.simulation.world, self.simulation.player.loadout));
+        self.replay_message = None;
+        // A high score left unnamed on the Game Over screen is kept before the next run starts.
+        self.submit_score();
+        self.trail.clear();
+        self.feedback.clear();
+        self.popups.clear();
+        self.particles.clear();
+        self.set_state(State::Game);
+    }
+
+    fn run_mode(&self) -> RunMode {
+        match &self.simulation.level {
+            Some(run) => RunMode::Level(run.level.name.clone()),
+            None => RunMode::Endless(self.simulation.preset),
+        }
+    }
+
+    // Adds the finished run to the stats, offers a leaderboard spot for endless
+    // runs, and keeps the replay on disk so it can be watched or attached to a bug report.
+    fn end_run(&mut self) {
+        let points = self.simulation.player.points;
+        let duration = self.simulation.clock.now();
+        self.save_data.lifetime.add_run(&self.simulation.stats, points, duration);
+        if self.simulation.level.is_none() && leaderboard::qualifies(&self.save_data.leaderboard, points) {
+            self.pending_score = Some(ScoreEntry {
+                name: String::new(),
+                score: points,
+                date: today(),
+                seed: self.simulation.seed,
+                mode: self.run_mode(),
+                duration,
+                scraps: self.simulation.stats.scraps,
+                asteroids_hit: self.simulation.stats.asteroids_hit,
+                best_combo: self.simulation.stats.best_combo,
+            });
+        }
+        if let Some(recording) = self.recording.take() {
+            let _ = std::fs::create_dir_all(self.paths.replay_dir());
+            if let Err(error) = recording.save(&self.paths.last_replay_file()) {
+                println!("Could not save replay: {}", error);
             }
-            let scrap_texture: Texture2D = space_object_textures[scrap_texture_number as usize];
-            j.push(SpaceObject::new(scrap_texture));
         }
-        let mut a = Vec::new();
-        let number_of_asteriods = gen_range(5, 10);
-        for _ in 0..number_of_asteriods {
-            let asteroid_texture: Texture2D = space_object_textures[1];
-            a.push(SpaceObject::new(asteroid_texture));
+    }
+
+    fn submit_score(&mut self) {
+        if let Some(mut entry) = self.pending_score.take() {
+            let name = self.name_input.trim();
+            entry.name = if name.is_empty() { String::from("Player") } else { name.to_string() };
+            leaderboard::insert(&mut self.save_data.leaderboard, entry);
         }
-        let mut p = Player::new(player_texture);
-        match std::fs::read("data/player.json") {
-            Ok(_) => {
-                Self{ player: p.load_player(player_texture, "data/player.json".to_string()), scraps: j, asteroids: a, debug: false, paused: false, state: State::MainMenu, previous_state: State::MainMenu, game_music: game_music, game_sounds: game_sounds, music_timer: Timer::new(13.0, true), music_volume: 25.0, sound_volume: 25.0 }
+    }
+
+    fn draw_leaderboard(&mut self) {
+        clear_background(BLACK);
+        let window_frame = egui::containers::Frame{
+            fill: egui::Color32::TRANSPARENT,
+            ..Default::default()
+        };
+        egui_macroquad::ui(|egui_ctx| {
+            egui::Window::new("egui ❤ macroquad")
+                .title_bar(false)
+                .frame(window_frame)
+                .default_pos(Pos2{ x: 20.0, y: 20.0 })
+                .resizable(false)
+                .show(egui_ctx, |ui| {
+                    egui_ctx.set_pixels_per_point(2.0 * self.settings.ui_scale);
+                    ui.label("Leaderboard");
+                    egui::ScrollArea::vertical().max_height(screen_height() / 2.0 * 0.6).show(ui, |ui| {
+                        egui::Grid::new("leaderboard").striped(true).show(ui, |ui| {
+                            for heading in ["#", "Name", "Score", "Mode", "Time", "Scraps", "Hits", "Combo", "Seed", "Date"] {
+                                ui.label(heading);
+                            }
+                            ui.end_row();
+                            for (rank, entry) in self.save_data.leaderboard.iter().enumerate() {
+                                ui.label(format!("{}", rank + 1));
+                                ui.label(&entry.name);
+                                ui.label(format!("{}", entry.score));
+                                ui.label(entry.mode_label());
+                                ui.label(format_duration(entry.duration));
+                                ui.label(format!("{}", entry.scraps));
+                                ui.label(format!("{}", entry.asteroids_hit));
+                                ui.label(format!("{}", entry.best_combo));
+                                ui.label(format!("{}", entry.seed));
+                                ui.label(&entry.date);
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    ui.separator();
+                    let lifetime = &self.save_data.lifetime;
+                    ui.label(format!("Runs: {}   Total playtime: {}", lifetime.runs, format_duration(lifetime.playtime)));
+                    ui.label(format!("Lifetime points: {}   Scraps collected: {}   Asteroids hit: {}   Best combo: {}", lifetime.points, lifetime.scraps, lifetime.asteroids_hit, lifetime.best_combo));
+                    if ui.button("Back").clicked() {
+                        self.set_state(self.previous_state);
+                    }
+                });
+        });
+
+        egui_macroquad::draw();
+    }
+
+    fn save_replay_copy(&mut self) {
+        let path = self.paths.replay_dir().join(format!("run-{}-{}.json", self.simulation.seed, miniquad::date::now() as u64));
+        self.replay_message = match std::fs::copy(self.paths.last_replay_file(), &path) {
+            Ok(_) => Some(format!("Saved to {}", path.display())),
+            Err(error) => Some(format!("Could not save replay: {}", error)),
+        };
+    }
+
+    fn start_replay(&mut self, path: &Path) {
+        let replay = match Replay::load(path) {
+            Ok(replay) => replay,
+            Err(error) => {
+                println!("Could not load replay {}: {}", path.display(), error);
+                return;
+            },
+        };
+        self.simulation.resize(vec2(replay.world[0], replay.world[1]));
+        self.simulation.loadout = replay.loadout;
+        match &replay.mode {
+            RunMode::Endless(preset) => self.simulation.start_endless(*preset, replay.seed),
+            RunMode::Level(name) => {
+                let index = match self.levels.levels.iter().position(|level| level.name == *name) {
+                    Some(index) => index,
+                    None => {
+                        println!("Could not load replay {}: unknown level '{}'", path.display(), name);
+                        return;
+                    },
+                };
+                self.simulation.seed = replay.seed;
+                self.simulation.start_level(index, &self.levels.levels[index].clone());
+            },
+        }
+        self.timestep.set_tick_rate(replay.tick_rate);
+        self.replay = Some(ReplayPlayer::new(replay));
+        self.trail.clear();
+        self.feedback.clear();
+        self.popups.clear();
+        self.particles.clear();
+        self.set_state(State::Replay);
+    }
+
+    fn run_tick(&mut self, input: &InputSnapshot) {
+        for event in self.simulation.step(input, self.timestep.tick as f32) {
+            self.handle_event(event);
+        }
+    }
+
+    fn quit(&mut self) -> ! {
+        self.submit_score();
+        self.save_game();
+        exit_game();
+    }
+
+    fn handle_event(&mut self, event: SimEvent) {
+        match event {
+            SimEvent::Pickup { points, combo, position, archetype } => {
+                match self.object_sounds[archetype] {
+                    Some(sound) => self.audio.play(sound),
+                    None => self.audio.play_sfx(Sfx::Pickup),
+                }
+                self.particles.burst(&SPARKLE, position, 10);
+                if combo > 1 {
+                    self.popups.add(format!("+{} x{}", points, combo), position, GOLD);
+                }
+                else {
+                    self.popups.add(format!("+{}", points), position, WHITE);
+                }
+            },
+            SimEvent::PowerUp { archetype, .. } => {
+                match self.object_sounds[archetype] {
+                    Some(sound) => self.audio.play(sound),
+                    None => self.audio.play_sfx(Sfx::Pickup),
+                }
+            },
+            SimEvent::Destroyed { points, position, archetype } => {
+                match self.object_sounds[archetype] {
+                    Some(sound) => self.audio.play(sound),
+                    None => self.audio.play_sfx(Sfx::Hit),
+                }
+                self.particles.burst(&DEBRIS, position, 24);
+                if points > 0 {
+                    self.popups.add(format!("+{}", points), position, WHITE);
+                }
+            },
+            SimEvent::Hit { position, archetype, shielded, .. } => {
+                match self.object_sounds[archetype] {
+                    Some(sound) => self.audio.play(sound),
+                    None => self.audio.play_sfx(Sfx::Hit),
+                }
+                self.feedback.hit(if shielded { 0.4 } else { 0.8 }, self.settings.rumble);
+                self.particles.burst(&DEBRIS, position, 10);
+            },
+            // Watching a replay never changes progress or leaves the replay.
+            SimEvent::LevelComplete { .. } | SimEvent::GameOver if self.replay.is_some() => {},
+            SimEvent::LevelComplete { stars } => {
+                if let Some(run) = &self.simulation.level {
+                    let record = LevelRecord { stars, score: self.simulation.player.points, time: self.simulation.clock.now() };
+                    let best = self.save_data.levels.entry(run.level.name.clone()).or_default();
+                    if record.is_better_than(best) {
+                        *best = record;
+                    }
+                }
+                self.end_run();
+                self.set_state(State::GameOver);
             },
-            Err(_) => {
-                Self{ player: Player::new(player_texture), scraps: j, asteroids: a, debug: false, paused: false, state: State::MainMenu, previous_state: State::MainMenu, game_music: game_music, game_sounds: game_sounds, music_timer: Timer::new(13.0, true), music_volume: 25.0, sound_volume: 25.0 }
+            SimEvent::GameOver => {
+                self.end_run();
+                self.set_state(State::GameOver);
             },
         }
     }
 
+    fn read_input(&self) -> InputSnapshot {
+        InputSnapshot {
+            left: is_key_down(self.settings.key_bindings.key(Action::MoveLeft)),
+            right: is_key_down(self.settings.key_bindings.key(Action::MoveRight)),
+            touches: touches().iter().map(|touch| touch.position.x).collect(),
+        }
+    }
+}
+
+impl Game for GameStruct {
+    fn new(content: Content, levels: LevelSet, assets: Assets) -> Self {
+        let Assets { player_texture, skin_textures, object_textures, object_sounds, game_music, game_sounds } = assets;
+        let world = vec2(screen_width(), screen_height());
+        let paths = Paths::resolve();
+        paths.create_dirs();
+        let settings = Settings::load(&paths.settings_file());
+        let legacy_save = Path::new(save::LEGACY_SAVE_PATH);
+        let save_data = if !paths.save_file().exists() && legacy_save.exists() {
+            save::load_or_default(legacy_save)
+        }
+        else {
+            save::load_or_default(&paths.save_file())
+        };
+        let mut player = Player::new(player_texture, world);
+        player.apply_def(&content.player);
+        player.apply_save(&save_data);
+        if let Some(texture) = skin_textures.get(selected_skin(&content.skins, &save_data)) {
+            player.texture = *texture;
+        }
+        let mut audio = AudioManager::new(game_music, game_sounds);
+        audio.define_playlist(Playlist::Menu, vec![0, 1]);
+        audio.define_playlist(Playlist::Gameplay, vec![1, 0]);
+        audio.define_playlist(Playlist::GameOver, vec![0]);
+        audio.set_volumes(settings.music_volume, settings.sound_volume);
+        audio.set_playlist(Playlist::Menu, &SystemClock);
+        let game_clock = PausableClock::new(SystemClock);
+        let mut simulation = Simulation::new(world, random_seed(), player, content, settings.difficulty);
+        simulation.loadout = save_data.upgrades.loadout();
+        Self {
+            paths,
+            simulation,
+            levels,
+            object_textures,
+            object_sounds,
+            skin_textures,
+            skin_preview: None,
+            trail: Vec::with_capacity(TRAIL_LENGTH + 1),
+            feedback: HitFeedback::new(Box::new(NoRumble)),
+            popups: Popups::new(),
+            particles: Particles::new(settings.particle_density),
+            exhaust: Emitter::new(&EXHAUST),
+            save_data,
+            autosave: Autosave::new(settings.autosave_interval, &game_clock),
+            debug: false,
+            paused: false,
+            state: State::MainMenu,
+            previous_state: State::MainMenu,
+            audio,
+            game_clock,
+            timestep: FixedTimestep::new(settings.tick_rate, MAX_CATCH_UP_TICKS),
+            settings,
+            rebinding: None,
+            seed_input: String::new(),
+            recording: None,
+            replay: None,
+            replay_message: None,
+            pending_score: None,
+            name_input: String::new(),
+        }
+    }
+
     fn update(&mut self) {
+        self.audio.set_volumes(self.settings.music_volume, self.settings.sound_volume);
+        self.audio.update(&SystemClock);
         match self.state {
-            State::MainMenu => {
-                if self.music_timer.is_timer_done() {
-                    let song_choice = gen_range(0, 1);
-                    stop_sound(self.game_music[0]);
-                    stop_sound(self.game_music[1]);
-                    play_sound(self.game_music[song_choice as usize], PlaySoundParams { looped: false, volume: self.music_volume });
-                    self.music_timer = Timer::new(13.0, false);
+            State::MainMenu => {},
+            State::Credits => {},
+            State::Options => {
+                if let Some(action) = self.rebinding {
+                    if let Some(key) = get_last_key_pressed() {
+                        // Keys that can't be bound leave the prompt open for another try.
+                        if self.settings.key_bindings.bind(action, key) {
+                            self.rebinding = None;
+                        }
+                    }
                 }
             },
-            State::Credits => {},
-            State::Options => {},
             State::GameTutorial => {},
             State::Game => {
-                if is_key_pressed(KeyCode::B) {
-                    self.player.save_player();
+                if is_key_pressed(self.settings.key_bindings.key(Action::Save)) {
+                    self.save_game();
                 }
-                if is_key_pressed(KeyCode::P) || is_key_pressed(KeyCode::Escape) {
+                if is_key_pressed(self.settings.key_bindings.key(Action::Pause)) || is_key_pressed(KeyCode::Escape) {
                     self.paused = !self.paused;
+                    self.game_clock.set_paused(self.paused);
+                    self.simulation.set_paused(self.paused);
+                    self.timestep.reset();
                 }
-                if self.paused == false {
-                    if is_key_pressed(KeyCode::G) {
+                if !self.paused {
+                    if is_key_pressed(self.settings.key_bindings.key(Action::Debug)) {
                         self.debug = !self.debug;
                     }
-                    if self.player.health <= 0 {
-                        self.state = State::GameOver;
-                    }
-                    let speed = calculate_speed(self.player.points);
-                    if is_key_down(KeyCode::A) && self.player.get_x() > 0.0 {
-                        self.player.move_x(-(speed * get_frame_time()));
+                    if self.autosave.is_due(&self.game_clock) {
+                        self.save_game();
                     }
-                    if is_key_down(KeyCode::D) && self.player.get_x() < screen_width() - self.player.get_width() {
-                        self.player.move_x(speed * get_frame_time());
-                    }
-                    for touch in touches().iter_mut() {
-                        if touch.position.x > self.player.get_x() {
-                            self.player.move_x(speed * get_frame_time());
-                        }
-                        else if touch.position.x < self.player.get_x() {
-                            self.player.move_x(-(speed * get_frame_time()));
+                    let input = self.read_input();
+                    let world = vec2(screen_width(), screen_height());
+                    self.simulation.resize(world);
+                    let ticks = self.timestep.advance(get_frame_time() as f64);
+                    for _ in 0..ticks {
+                        if let Some(recording) = self.recording.as_mut() {
+                            recording.record_resize(world);
+                            recording.record(&input);
                         }
+                        self.run_tick(&input);
                     }
-                    for junk in self.scraps.iter_mut() {
-                        if junk.position.y > screen_height() {
-                            junk.reset();
-                        }
-                        else if junk.position.overlaps(&self.player.get_rect()) {
-                            junk.hit(self.game_sounds[1], self.sound_volume);
-                            self.player.collect_point(junk.get_points());
-                        }
-                        else {
-                            junk.move_y(speed / 2.0 * get_frame_time());
-                        }
-                    }
-                    for asteroid in self.asteroids.iter_mut() {
-                        if asteroid.position.y > screen_height() {
-                            asteroid.reset()
-                        }
-                        else if asteroid.position.overlaps(&self.player.get_rect()) {
-                            asteroid.hit(self.game_sounds[0], self.sound_volume);
-                            self.player.hurt(asteroid.get_health());
-                        }
-                        else {
-                            asteroid.move_y(speed / 2.0 * get_frame_time());
+                }
+            },
+            State::Replay => {
+                let (paused, speed, step) = match self.replay.as_mut() {
+                    Some(player) => {
+                        if is_key_pressed(self.settings.key_bindings.key(Action::Pause)) {
+                            player.paused = !player.paused;
                         }
+                        (player.paused, player.speed, std::mem::take(&mut player.step))
+                    },
+                    None => return,
+                };
+                if is_key_pressed(KeyCode::Escape) {
+                    self.set_state(State::MainMenu);
+                    return;
+                }
+                self.timestep.max_ticks = MAX_CATCH_UP_TICKS * speed;
+                let ticks = if paused {
+                    step as u32
+                }
+                else {
+                    self.timestep.advance(get_frame_time() as f64 * speed as f64)
+                };
+                for _ in 0..ticks {
+                    let tick = match self.replay.as_mut().and_then(|player| player.next()) {
+                        Some(tick) => tick,
+                        None => break,
+                    };
+                    if let Some(world) = tick.resize {
+                        self.simulation.resize(world);
                     }
+                    self.run_tick(&tick.input);
                 }
             },
             State::Shop => {},
+            State::LevelSelect => {},
+            State::Leaderboard => {},
             State::GameOver => {},
         }
     }
@@ -281,7 +800,7 @@ impl Game for GameStruct {
                         .default_pos(Pos2{ x: (screen_width() / 2.0 + 250.0) / 3.0, y: (screen_height() / 2.0 + 200.0) / 3.0 })
                         .resizable(false)
                         .show(egui_ctx, |ui| {
-                            egui_ctx.set_pixels_per_point(3.0);
+                            egui_ctx.set_pixels_per_point(3.0 * self.settings.ui_scale);
                             ui.label("Credits");
                             ui.hyperlink_to("Code, Music, and Sound FX made by Anatoliy K.", "https://linktr.ee/anatoliyk05");
                             ui.hyperlink_to("Textures made by @happyghost_fren on Instagram", "https://www.instagram.com/happyghost_fren/");
@@ -290,7 +809,7 @@ impl Game for GameStruct {
                             ui.hyperlink_to("Built using Macroquad", "https://macroquad.rs");
                             ui.hyperlink_to("and egui", "https://egui.rs");
                             if ui.button("Back").clicked() {
-                                self.state = self.previous_state.clone();
+                                self.set_state(self.previous_state);
                             }
                         });
                 });
@@ -299,7 +818,7 @@ impl Game for GameStruct {
             },
             State::GameTutorial => {
                 clear_background(BLACK);
-                for asteroid in self.asteroids.iter_mut() {
+                for asteroid in self.simulation.asteroids.iter().take(1) {
                     let asteroid_parmas = DrawTextureParams{
                         dest_size: Some(Vec2{ x: asteroid.position.w, y: asteroid.position.h}),
                         source: None,
@@ -308,10 +827,10 @@ impl Game for GameStruct {
                         flip_y: false,
                         pivot: None,
                     };
-                    draw_texture_ex(asteroid.texture, (screen_width() / 10.0 * 4.5 + (asteroid.position.w / 2.0)) - 150.0, screen_height() / 2.0, WHITE, asteroid_parmas);
+                    draw_texture_ex(self.object_textures[asteroid.archetype], (screen_width() / 10.0 * 4.5 + (asteroid.position.w / 2.0)) - 150.0, screen_height() / 2.0, WHITE, asteroid_parmas);
                     draw_text("Avoid the Asteroids", (screen_width() / 10.0 * 4.5 + (asteroid.position.w / 2.0)) - 250.0, screen_height() / 2.0 + asteroid.position.h + 25.0, 25.0, WHITE);
                 }
-                for scrap in self.scraps.iter_mut() {
+                for scrap in self.simulation.scraps.iter().take(1) {
                     let scrap_parmas = DrawTextureParams{
                         dest_size: Some(Vec2{ x: scrap.position.w, y: scrap.position.h}),
                         source: None,
@@ -320,7 +839,7 @@ impl Game for GameStruct {
                         flip_y: false,
                         pivot: None,
                     };
-                    draw_texture_ex(scrap.texture, screen_width() / 10.0 * 4.5 + (scrap.position.w / 2.0), screen_height() / 2.0, WHITE, scrap_parmas);
+                    draw_texture_ex(self.object_textures[scrap.archetype], screen_width() / 10.0 * 4.5 + (scrap.position.w / 2.0), screen_height() / 2.0, WHITE, scrap_parmas);
                     draw_text("Catch the Scraps", screen_width() / 10.0 * 4.5 + (scrap.position.w / 2.0), screen_height() / 2.0 + scrap.position.h + 25.0, 25.0, WHITE);
                 }
                 let play_rect = Rect { 
@@ -338,72 +857,167 @@ impl Game for GameStruct {
                 );
                 play_button.draw();
                 if play_button.clicked() {
-                    self.state = State::Game;
+                    self.begin_run();
                 }
             },
-            State::Game => {
+            State::Game | State::Replay => {
                 clear_background(BLACK);
 
                 if self.paused {
                     self.draw_pause();
                 }
 
-                for scrap in self.scraps.iter() {
-                    //draw_circle(junk.position.x, junk.position.y, junk.position.r, RED);
+                let alpha = self.timestep.alpha();
+
+                let frozen = self.frozen();
+                if !frozen {
+                    self.feedback.update(get_frame_time());
+                    self.popups.update(get_frame_time());
+                    self.particles.density = self.settings.particle_density;
+                    self.particles.update(get_frame_time());
+                }
+                let shake = if frozen || self.settings.accessibility.reduce_motion { Vec2::ZERO } else { self.feedback.shake_offset() };
+                set_camera(&Camera2D::from_display_rect(Rect::new(shake.x, shake.y, screen_width(), screen_height())));
+
+                for scrap in self.simulation.scraps.iter().filter(|scrap| scrap.active) {
+                    let scrap_rect = scrap.draw_rect(alpha);
                     let scrap_parmas = DrawTextureParams{
-                        dest_size: Some(Vec2{ x: scrap.position.w, y: scrap.position.h}),
+                        dest_size: Some(Vec2{ x: scrap_rect.w, y: scrap_rect.h}),
                         source: None,
-                        rotation: scrap.rotate,
+                        rotation: scrap.draw_rotation(alpha),
                         flip_x: false,
                         flip_y: false,
                         pivot: None,
                     };
-                    draw_texture_ex(scrap.texture, scrap.position.x, scrap.position.y, WHITE, scrap_parmas);
+                    draw_texture_ex(self.object_textures[scrap.archetype], scrap_rect.x, scrap_rect.y, WHITE, scrap_parmas);
                     if self.debug {
-                        draw_rectangle_lines(scrap.position.x, scrap.position.y, scrap.position.w, scrap.position.h, 5.0, BLUE);
+                        scrap.shape.transformed(scrap_rect, scrap.draw_rotation(alpha)).draw_lines(3.0, BLUE);
                     }
                 }
 
-                for asteroid in self.asteroids.iter() {
-                    //draw_circle(junk.position.x, junk.position.y, junk.position.r, RED);
+                for asteroid in self.simulation.asteroids.iter().filter(|asteroid| asteroid.active) {
+                    let asteroid_rect = asteroid.draw_rect(alpha);
                     let asteroid_parmas = DrawTextureParams{
-                        dest_size: Some(Vec2{ x: asteroid.position.w, y: asteroid.position.h}),
+                        dest_size: Some(Vec2{ x: asteroid_rect.w, y: asteroid_rect.h}),
+                        source: None,
+                        rotation: asteroid.draw_rotation(alpha),
+                        flip_x: false,
+                        flip_y: false,
+                        pivot: None,
+                    };
+                    draw_texture_ex(self.object_textures[asteroid.archetype], asteroid_rect.x, asteroid_rect.y, WHITE, asteroid_parmas);
+                    if self.debug {
+                        asteroid.shape.transformed(asteroid_rect, asteroid.draw_rotation(alpha)).draw_lines(3.0, BLUE);
+                    }
+                }
+
+                for power_up in self.simulation.power_ups.iter().filter(|power_up| power_up.active) {
+                    let power_up_rect = power_up.draw_rect(alpha);
+                    let power_up_parmas = DrawTextureParams{
+                        dest_size: Some(Vec2{ x: power_up_rect.w, y: power_up_rect.h}),
                         source: None,
-                        rotation: asteroid.rotate,
+                        rotation: power_up.draw_rotation(alpha),
                         flip_x: false,
                         flip_y: false,
                         pivot: None,
                     };
-                    draw_texture_ex(asteroid.texture, asteroid.position.x, asteroid.position.y, WHITE, asteroid_parmas);
+                    draw_texture_ex(self.object_textures[power_up.archetype], power_up_rect.x, power_up_rect.y, WHITE, power_up_parmas);
                     if self.debug {
-                        draw_rectangle_lines(asteroid.position.x, asteroid.position.y, asteroid.position.w, asteroid.position.h, 5.0, BLUE);
+                        power_up.shape.transformed(power_up_rect, power_up.draw_rotation(alpha)).draw_lines(3.0, BLUE);
                     }
                 }
 
-                //draw_rectangle(self.player.position.x, self.player.position.y, self.player.position.w, self.player.position.h, GREEN);
+                for bolt in self.simulation.cannon.bolts.iter().filter(|bolt| bolt.active) {
+                    let bolt_position = bolt.draw_position(alpha);
+                    draw_rectangle(bolt_position.x - 2.0, bolt_position.y - 7.0, 4.0, 14.0, SKYBLUE);
+                }
+
+                let player_rect = self.simulation.player.draw_rect(alpha);
+                if !frozen && !self.simulation.game_over {
+                    let motion_x = self.simulation.player.motion().x;
+                    let nozzle = vec2(player_rect.x + player_rect.w / 2.0, player_rect.y + player_rect.h * 0.9);
+                    // Burns harder while steering.
+                    let throttle = if motion_x != 0.0 { 1.6 } else { 1.0 };
+                    self.particles.emit(&mut self.exhaust, nozzle, exhaust_direction(motion_x), throttle, get_frame_time());
+                }
+                self.particles.draw();
+
+                let skin = selected_skin(&self.simulation.content.skins, &self.save_data);
+                if let (Some(color), false) = (self.skin_trail(skin), self.settings.accessibility.reduce_motion) {
+                    self.update_trail(vec2(player_rect.x + player_rect.w / 2.0, player_rect.y + player_rect.h));
+                    self.draw_trail(&self.trail, color);
+                }
                 let player_parmas = DrawTextureParams{
-                    dest_size: Some(Vec2{ x: self.player.get_width(), y: self.player.get_height()}),
+                    dest_size: Some(Vec2{ x: player_rect.w, y: player_rect.h}),
                     source: None,
                     rotation: 0.0,
                     flip_x: false,
                     flip_y: false,
                     pivot: None,
                 };
-                draw_texture_ex(self.player.texture, self.player.get_x(), self.player.get_y(), WHITE, player_parmas);
+                // Blink while invulnerable, or just fade with reduced motion.
+                let player_color = if !self.simulation.player.is_invulnerable() {
+                    WHITE
+                }
+                else if self.settings.accessibility.reduce_motion || (self.simulation.invulnerable_for() * 10.0) as i32 % 2 == 0 {
+                    Color::new(1.0, 1.0, 1.0, 0.5)
+                }
+                else {
+                    Color::new(1.0, 1.0, 1.0, 0.15)
+                };
+                draw_texture_ex(self.simulation.player.texture, player_rect.x, player_rect.y, player_color, player_parmas);
                 if self.debug {
-                    draw_rectangle_lines(self.player.get_x(), self.player.get_y(), self.player.get_width(), self.player.get_height(), 5.0, BLUE);
+                    self.simulation.player.shape.transformed(player_rect, 0.0).draw_lines(3.0, BLUE);
                 }
 
-                draw_text(&format!("Points: {}", self.player.points).to_owned(), 50.0, 50.0, 25.0, WHITE);
+                self.popups.draw(self.settings.text_size(), !self.settings.accessibility.reduce_motion);
+
+                set_default_camera();
+                self.feedback.draw_vignette();
+
+                let text_size = self.settings.text_size();
+
+                draw_text(&format!("Points: {}", self.simulation.player.points).to_owned(), 50.0, 50.0, text_size, WHITE);
+
+                draw_text(&format!("Health: {}", self.simulation.player.health).to_owned(), screen_width() / 10.0 * 8.5, 50.0, text_size, WHITE);
+
+                if self.simulation.player.shields > 0 {
+                    draw_text(&format!("Shields: {}", self.simulation.player.shields), screen_width() / 10.0 * 8.5, 50.0 + text_size, text_size, SKYBLUE);
+                }
 
-                draw_text(&format!("Health: {}", self.player.health).to_owned(), screen_width() / 10.0 * 8.5, 50.0, 25.0, WHITE);
+                self.draw_effects(50.0, 50.0 + text_size * 2.0);
+
+                let combo = self.simulation.combo;
+                if combo.count > 1 {
+                    let combo_y = 50.0 + text_size * 2.0 + 80.0;
+                    draw_text(&format!("Combo x{} ({})", combo.multiplier(), combo.count), 50.0, combo_y, text_size, GOLD);
+                    draw_rectangle(50.0, combo_y + 8.0, 150.0 * combo.fraction_left(self.simulation.clock.now()), 6.0, GOLD);
+                }
+
+                if self.autosave.show_indicator(&self.game_clock) {
+                    draw_text("Saved", 50.0, 50.0 + text_size, text_size, GRAY);
+                }
 
                 if self.debug {
                     draw_text(&format!("FPS: {}", get_fps()).to_owned(), screen_width() / 2.0, 50.0, 25.0, WHITE);
+                    if let Some(pattern) = self.simulation.director.pattern() {
+                        draw_text(&format!("Wave: {:?}", pattern), screen_width() / 2.0, 75.0, 25.0, WHITE);
+                    }
+                    if let (Some(band), Some(lane)) = (self.simulation.director.band(), self.simulation.director.lane()) {
+                        draw_rectangle(band.left, 0.0, band.right - band.left, self.simulation.world.y, Color::new(0.0, 1.0, 0.0, 0.1));
+                        draw_line(lane, 0.0, lane, self.simulation.world.y, 1.0, GREEN);
+                    }
+                    for (line, effect) in self.simulation.player.effects.iter().enumerate() {
+                        draw_text(&format!("{}: {:.1}s x{}", effect.kind.label(), effect.remaining, effect.stacks), screen_width() / 2.0, 100.0 + line as f32 * 25.0, 25.0, WHITE);
+                    }
                 }
                 /*if self.paused {
                     egui_macroquad::draw();
                 }*/
+
+                if let State::Replay = self.state {
+                    self.draw_replay_controls();
+                }
             },
             State::Options => {
                 let window_frame = egui::containers::Frame{
@@ -417,12 +1031,51 @@ impl Game for GameStruct {
                         .default_pos(Pos2{ x: (screen_width() / 2.0 + 250.0) / 3.0, y: (screen_height() / 2.0 + 200.0) / 3.0 })
                         .resizable(false)
                         .show(egui_ctx, |ui| {
-                            egui_ctx.set_pixels_per_point(3.0);
+                            egui_ctx.set_pixels_per_point(3.0 * self.settings.ui_scale);
                             ui.label("Options");
-                            ui.add(egui::widgets::Slider::new(&mut self.music_volume, 0.0..=100.0).text("Music Volume"));
-                            ui.add(egui::widgets::Slider::new(&mut self.sound_volume, 0.0..=100.0).text("Sound Volume"));
+                            egui::ScrollArea::vertical().max_height(screen_height() / 3.0 * 0.6).show(ui, |ui| {
+                                ui.add(egui::widgets::Slider::new(&mut self.settings.music_volume, 0.0..=100.0).text("Music Volume"));
+                                ui.add(egui::widgets::Slider::new(&mut self.settings.sound_volume, 0.0..=100.0).text("Sound Volume"));
+                                ui.separator();
+                                ui.label("Controls");
+                                for action in Action::ALL {
+                                    ui.horizontal(|ui| {
+                                        ui.label(action.label());
+                                        let key_label = if self.rebinding == Some(action) {
+                                            String::from("Press a key...")
+                                        }
+                                        else {
+                                            self.settings.key_bindings.name(action).to_string()
+                                        };
+                                        if ui.button(key_label).clicked() {
+                                            self.rebinding = Some(action);
+                                        }
+                                    });
+                                }
+                                ui.separator();
+                                ui.label("Display");
+                                ui.checkbox(&mut self.settings.fullscreen, "Fullscreen");
+                                ui.add(egui::widgets::DragValue::new(&mut self.settings.window_width).clamp_range(WINDOW_WIDTH_RANGE).prefix("Width "));
+                                ui.add(egui::widgets::DragValue::new(&mut self.settings.window_height).clamp_range(WINDOW_HEIGHT_RANGE).prefix("Height "));
+                                ui.add(egui::widgets::Slider::new(&mut self.settings.ui_scale, UI_SCALE_RANGE).text("UI Scale"));
+                                ui.add(egui::widgets::Slider::new(&mut self.settings.tick_rate, 30..=240).text("Simulation Rate (Hz)"));
+                                ui.add(egui::widgets::Slider::new(&mut self.settings.particle_density, 0.0..=1.0).text("Particle Density"));
+                                egui::ComboBox::from_label("Language")
+                                    .selected_text(LANGUAGES.iter().find(|(code, _)| *code == self.settings.language).map(|(_, name)| *name).unwrap_or("English"))
+                                    .show_ui(ui, |ui| {
+                                        for (code, name) in LANGUAGES {
+                                            ui.selectable_value(&mut self.settings.language, code.to_string(), name);
+                                        }
+                                    });
+                                ui.separator();
+                                ui.label("Accessibility");
+                                ui.checkbox(&mut self.settings.accessibility.reduce_motion, "Reduce Motion");
+                                ui.checkbox(&mut self.settings.accessibility.large_text, "Large Text");
+                                ui.checkbox(&mut self.settings.rumble, "Controller Rumble");
+                                ui.add(egui::widgets::Slider::new(&mut self.settings.autosave_interval, 0.0..=300.0).text("Autosave Interval (s, 0 = off)"));
+                            });
                             if ui.button("Back").clicked() {
-                                self.state = self.previous_state.clone();
+                                self.set_state(self.previous_state);
                             }
                         });
                 });
@@ -442,29 +1095,146 @@ impl Game for GameStruct {
                         .default_pos(Pos2{ x: (screen_width() / 2.0 + 250.0) / 3.0, y: (screen_height() / 2.0 + 200.0) / 3.0 })
                         .resizable(false)
                         .show(egui_ctx, |ui| {
-                            egui_ctx.set_pixels_per_point(3.0);
+                            egui_ctx.set_pixels_per_point(3.0 * self.settings.ui_scale);
                             ui.label("Shop");
-                            ui.label(format!("Points {} Coins {}", self.player.points, self.player.coins));
-                            if ui.button("Convert Points to Coins").clicked() {
-                                let new_coins = self.player.points / 10;
-                                self.player.coins += new_coins;
-                                self.player.points -= new_coins;
+                            ui.label(format!("Points {} Coins {}", self.simulation.player.points, self.simulation.player.coins));
+                            // Points are part of the run being played, and changing them mid-run would desync its replay.
+                            let run_paused = matches!(self.previous_state, State::Game);
+                            if ui.add_enabled(!run_paused, egui::Button::new("Convert Points to Coins")).clicked() {
+                                let new_coins = self.simulation.player.points / 10;
+                                self.simulation.player.coins += new_coins;
+                                self.simulation.player.points -= new_coins * 10;
                             }
-                            let player_parmas = DrawTextureParams{
-                                dest_size: Some(Vec2{ x: self.player.get_width(), y: self.player.get_height()}),
-                                source: None,
-                                rotation: 0.0,
-                                flip_x: false,
-                                flip_y: false,
-                                pivot: None,
-                            };
-                            draw_texture_ex(self.player.texture, self.player.get_x(), self.player.get_y(), WHITE, player_parmas);
+                            ui.separator();
+                            egui::ScrollArea::vertical().max_height(screen_height() / 3.0 * 0.5).show(ui, |ui| {
+                                for upgrade in Upgrade::ALL {
+                                    let tier = self.save_data.upgrades.tier(upgrade);
+                                    ui.horizontal(|ui| {
+                                        ui.label(format!("{} {}/{}", upgrade.label(), tier, upgrade.prices().len()));
+                                        match self.save_data.upgrades.next_price(upgrade) {
+                                            Some(price) => {
+                                                let affordable = self.simulation.player.coins >= price;
+                                                if ui.add_enabled(affordable, egui::Button::new(format!("Buy ({} coins)", price))).clicked()
+                                                    && self.save_data.upgrades.buy(upgrade, &mut self.simulation.player.coins) {
+                                                    self.simulation.loadout = self.save_data.upgrades.loadout();
+                                                }
+                                            },
+                                            None => {
+                                                ui.label("Maxed");
+                                            },
+                                        }
+                                    });
+                                    ui.label(upgrade.description());
+                                }
+                            });
+                            ui.label("Upgrades take effect from your next run.");
+                            ui.separator();
+                            ui.label("Skins");
+                            let mut preview = None;
+                            egui::ScrollArea::vertical().id_source("skins").max_height(screen_height() / 3.0 * 0.3).show(ui, |ui| {
+                                let selected = selected_skin(&self.simulation.content.skins, &self.save_data);
+                                for index in 0..self.simulation.content.skins.len() {
+                                    let skin = &self.simulation.content.skins[index];
+                                    let mut equip = false;
+                                    let row = ui.horizontal(|ui| {
+                                        ui.label(&skin.name);
+                                        if index == selected {
+                                            ui.label("Equipped");
+                                        }
+                                        else if skin_owned(skin, &self.save_data) {
+                                            equip = ui.button("Equip").clicked();
+                                        }
+                                        else if skin_for_sale(skin, &self.save_data) {
+                                            let affordable = self.simulation.player.coins >= skin.price;
+                                            equip = ui.add_enabled(affordable, egui::Button::new(format!("Buy ({} coins)", skin.price))).clicked()
+                                                && buy_skin(skin, &mut self.save_data, &mut self.simulation.player.coins);
+                                        }
+                                        else if let Some(unlock) = &skin.unlock {
+                                            ui.label(format!("Locked: {}", unlock.description()));
+                                        }
+                                    });
+                                    if row.response.hovered() {
+                                        preview = Some(index);
+                                    }
+                                    if equip {
+                                        self.save_data.skin = skin.name.clone();
+                                        self.simulation.player.texture = self.skin_textures[index];
+                                    }
+                                }
+                            });
+                            self.skin_preview = preview;
                             if ui.button("Back").clicked() {
-                                self.state = State::Game;
+                                self.set_state(self.previous_state);
                             }
                         });
                 });
-        
+
+                let skin = self.skin_preview.unwrap_or_else(|| selected_skin(&self.simulation.content.skins, &self.save_data));
+                let (width, height) = (self.simulation.player.get_width(), self.simulation.player.get_height());
+                let preview = vec2(screen_width() / 10.0 * 8.0 - width / 2.0, self.simulation.player.get_y());
+                if let Some(color) = self.skin_trail(skin) {
+                    let exhaust: Vec<Vec2> = (0..TRAIL_LENGTH).rev().map(|age| vec2(preview.x + width / 2.0, preview.y + height + age as f32 * 6.0)).collect();
+                    self.draw_trail(&exhaust, color);
+                }
+                let player_parmas = DrawTextureParams{
+                    dest_size: Some(Vec2{ x: width, y: height}),
+                    source: None,
+                    rotation: 0.0,
+                    flip_x: false,
+                    flip_y: false,
+                    pivot: None,
+                };
+                draw_texture_ex(self.skin_textures[skin], preview.x, preview.y, WHITE, player_parmas);
+
+                egui_macroquad::draw();
+            },
+            State::Leaderboard => {
+                self.draw_leaderboard();
+            },
+            State::LevelSelect => {
+                clear_background(BLACK);
+                let window_frame = egui::containers::Frame{
+                    fill: egui::Color32::TRANSPARENT,
+                    ..Default::default()
+                };
+                egui_macroquad::ui(|egui_ctx| {
+                    egui::Window::new("egui ❤ macroquad")
+                        .title_bar(false)
+                        .frame(window_frame)
+                        .default_pos(Pos2{ x: (screen_width() / 2.0 + 250.0) / 3.0, y: (screen_height() / 2.0 + 200.0) / 3.0 })
+                        .resizable(false)
+                        .show(egui_ctx, |ui| {
+                            egui_ctx.set_pixels_per_point(3.0 * self.settings.ui_scale);
+                            ui.label("Levels");
+                            egui::ScrollArea::vertical().max_height(screen_height() / 3.0 * 0.6).show(ui, |ui| {
+                                for index in 0..self.levels.levels.len() {
+                                    let level = &self.levels.levels[index];
+                                    let unlocked = self.levels.is_unlocked(index, &self.save_data.levels);
+                                    let best = match self.save_data.levels.get(&level.name) {
+                                        Some(record) => format!("{} ({} points)", "*".repeat(record.stars as usize), record.score),
+                                        None => String::new(),
+                                    };
+                                    let mut start = false;
+                                    ui.horizontal(|ui| {
+                                        start = ui.add_enabled(unlocked, egui::Button::new(&level.name)).clicked();
+                                        ui.label(best);
+                                    });
+                                    if !level.description.is_empty() {
+                                        ui.label(&level.description);
+                                    }
+                                    if start {
+                                        let level = level.clone();
+                                        self.simulation.start_level(index, &level);
+                                        self.begin_run();
+                                    }
+                                }
+                            });
+                            if ui.button("Back").clicked() {
+                                self.set_state(State::MainMenu);
+                            }
+                        });
+                });
+
                 egui_macroquad::draw();
             },
             State::GameOver => {
@@ -480,21 +1250,69 @@ impl Game for GameStruct {
                         .default_pos(Pos2{ x: (screen_width() / 2.0 + 250.0) / 3.0, y: (screen_height() / 2.0 + 200.0) / 3.0 })
                         .resizable(false)
                         .show(egui_ctx, |ui| {
-                            egui_ctx.set_pixels_per_point(3.0);
-                            ui.label(&format!("Game Over! You has {} points!", self.player.points).to_owned());
+                            egui_ctx.set_pixels_per_point(3.0 * self.settings.ui_scale);
+                            match self.simulation.level.as_ref().map(|run| (run.index, run.result)) {
+                                Some((index, Some(stars))) => {
+                                    ui.label(format!("Level Complete! {}", "*".repeat(stars as usize)));
+                                    ui.label(format!("{} points in {:.1} seconds", self.simulation.player.points, self.simulation.clock.now()));
+                                    if index + 1 < self.levels.levels.len() && ui.button("Next Level").clicked() {
+                                        self.simulation.start_level(index + 1, &self.levels.levels[index + 1]);
+                                        self.begin_run();
+                                    }
+                                },
+                                Some((_, None)) => {
+                                    ui.label(format!("Level Failed! You scored {} points!", self.simulation.player.points));
+                                },
+                                None => {
+                                    ui.label(format!("Game Over! You scored {} points!", self.simulation.player.points));
+                                },
+                            }
+                            let stats = self.simulation.stats;
+                            ui.label(format!("Time {}   Scraps {}   Asteroids hit {}   Destroyed {}   Best combo {}", format_duration(self.simulation.clock.now()), stats.scraps, stats.asteroids_hit, stats.asteroids_destroyed, stats.best_combo));
+                            if self.pending_score.is_some() {
+                                ui.label("New high score! Enter your name:");
+                                ui.horizontal(|ui| {
+                                    let response = ui.add(egui::TextEdit::singleline(&mut self.name_input).desired_width(120.0));
+                                    let entered = response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+                                    if ui.button("Submit").clicked() || entered {
+                                        self.submit_score();
+                                        self.save_game();
+                                    }
+                                });
+                            }
+                            ui.label(format!("Seed: {}", self.simulation.seed));
                             if ui.button("Play Again").clicked() {
-                                self.player.points = 0;
-                                self.player.health = 5;
-                                for space_scraps in self.scraps.iter_mut() {
-                                    space_scraps.reset();
+                                if self.simulation.level.is_none() {
+                                    self.simulation.seed = random_seed();
                                 }
-                                for asteroids in self.asteroids.iter_mut() {
-                                    asteroids.reset();
+                                self.simulation.restart();
+                                self.begin_run();
+                            }
+                            if self.simulation.level.is_none() && ui.button("Replay Seed").clicked() {
+                                self.simulation.restart();
+                                self.begin_run();
+                            }
+                            if self.simulation.level.is_some() && ui.button("Levels").clicked() {
+                                self.set_state(State::LevelSelect);
+                            }
+                            if ui.button("Leaderboard").clicked() {
+                                self.previous_state = State::GameOver;
+                                self.set_state(State::Leaderboard);
+                            }
+                            ui.horizontal(|ui| {
+                                if ui.button("Watch Replay").clicked() {
+                                    let path = self.paths.last_replay_file();
+                                    self.start_replay(&path);
+                                }
+                                if ui.button("Save Replay").clicked() {
+                                    self.save_replay_copy();
                                 }
-                                self.state = State::Game;
+                            });
+                            if let Some(message) = &self.replay_message {
+                                ui.label(message);
                             }
                             if ui.button("Quit").clicked() {
-                                exit_game();
+                                self.quit();
                             }
                         });
                 });
@@ -508,101 +1326,82 @@ impl Game for GameStruct {
 }
 
 fn window_conf() -> Conf {
+    let settings = Settings::load(&Paths::resolve().settings_file());
     Conf {
         window_title: "Space Cleanup".to_owned(),
         high_dpi: true,
-        window_height: 720,
-        window_width: 1280,
+        window_height: settings.window_height,
+        window_width: settings.window_width,
+        fullscreen: settings.fullscreen,
         ..Default::default()
     }
 }
 
-#[cfg(not(debug_assertions))] 
 #[macroquad::main(window_conf)]
 async fn main() {
-    let player_path: &str;
-    let scraps_path: &str;
-    let asteroid_path: &str;
-    let song1_path: &str;
-    let song2_path: &str;
-    let sound1_path: &str;
-    let sound2_path: &str;
-    if std::env::consts::OS.to_lowercase() == "macos".to_lowercase() {
-        player_path = "../Resources/res/player.png";
-        scraps_path = "../Resources/res/junk1.png";
-        asteroid_path = "../Resources/res/asteroid.png";
-        song1_path = "../Resources/res/music/song1.wav";
-        song2_path = "../Resources/res/music/song2.wav";
-        sound1_path = "../Resources/res/sounds/hit.wav";
-        sound2_path = "../Resources/res/sounds/pickup.wav";
-    }
-    else {
-        player_path = "res/player.png";
-        scraps_path = "res/junk1.png";
-        asteroid_path = "res/asteroid.png";
-        song1_path = "res/music/song1.wav";
-        song2_path = "res/music/song2.wav";
-        sound1_path = "res/sounds/hit.wav";
-        sound2_path = "res/sounds/pickup.wav";
-    }
-
-    let player_image = init_texture(player_path).await;
-
-    let mut junk_texture_list = Vec::new();
-    junk_texture_list.push(init_texture(scraps_path).await);
-    junk_texture_list.push(init_texture(asteroid_path).await);
-
-    let mut game_music = Vec::new();
-    game_music.push(init_sound(song1_path).await);
-    game_music.push(init_sound(song2_path).await);
-
-    let mut game_sounds = Vec::new();
-    game_sounds.push(init_sound(sound1_path).await);
-    game_sounds.push(init_sound(sound2_path).await);
-
-    let mut main_game = GameStruct::new(player_image, junk_texture_list, game_music, game_sounds);
-
-    loop { 
-
-        main_game.run();
-
-        next_frame().await
+    let mut content = init_content(&resource(CONTENT_PATH)).await;
 
+    let player_image = init_texture(&resource(&content.player.texture)).await;
+    if let Some(threshold) = alpha_threshold(&content.player.shape) {
+        let size = vec2(content.player.size[0], content.player.size[1]);
+        content.player.collision = alpha_hull(&init_image(&resource(&content.player.texture)).await, size, threshold);
+    }
+    for archetype in content.archetypes.iter_mut() {
+        if let Some(threshold) = alpha_threshold(&archetype.shape) {
+            let size = vec2(archetype.size[0], archetype.size[1]);
+            archetype.collision = Rc::new(alpha_hull(&init_image(&resource(&archetype.texture)).await, size, threshold));
+        }
     }
-}
 
-#[cfg(debug_assertions)]
-#[macroquad::main(window_conf)]
-async fn main() {
-    let player_path = "res/player.png";
-    let scraps_path = "res/junk1.png";
-    let asteroid_path = "res/asteroid.png";
-    let song1_path = "res/music/song1.wav";
-    let song2_path = "res/music/song2.wav";
-    let sound1_path = "res/sounds/hit.wav";
-    let sound2_path = "res/sounds/pickup.wav";
+    let levels = init_levels(&resource(LEVELS_PATH), &content).await;
 
-    let player_image = init_texture(player_path).await;
+    let mut skin_textures = Vec::new();
+    for skin in content.skins.iter() {
+        skin_textures.push(init_texture(&resource(&skin.texture)).await);
+    }
 
-    let mut junk_texture_list = Vec::new();
-    junk_texture_list.push(init_texture(scraps_path).await);
-    junk_texture_list.push(init_texture(asteroid_path).await);
+    let mut object_textures = Vec::new();
+    let mut object_sounds = Vec::new();
+    let mut loaded_sounds: HashMap<String, Sound> = HashMap::new();
+    for archetype in content.archetypes.iter() {
+        object_textures.push(init_texture(&resource(&archetype.texture)).await);
+        match &archetype.sound {
+            Some(path) => {
+                if !loaded_sounds.contains_key(path) {
+                    loaded_sounds.insert(path.clone(), init_sound(&resource(path)).await);
+                }
+                object_sounds.push(loaded_sounds.get(path).copied());
+            },
+            None => object_sounds.push(None),
+        }
+    }
 
     let mut game_music = Vec::new();
-    game_music.push(init_sound(song1_path).await);
-    game_music.push(init_sound(song2_path).await);
+    game_music.push(init_track(&resource("res/music/song1.wav")).await);
+    game_music.push(init_track(&resource("res/music/song2.wav")).await);
 
     let mut game_sounds = Vec::new();
-    game_sounds.push(init_sound(sound1_path).await);
-    game_sounds.push(init_sound(sound2_path).await);
+    game_sounds.push(init_sound(&resource("res/sounds/hit.wav")).await);
+    game_sounds.push(init_sound(&resource("res/sounds/pickup.wav")).await);
+
+    let assets = Assets { player_texture: player_image, skin_textures, object_textures, object_sounds, game_music, game_sounds };
+    let mut main_game = GameStruct::new(content, levels, assets);
+
+    if let Some(path) = replay_arg() {
+        main_game.start_replay(&path);
+    }
 
-    let mut main_game = GameStruct::new(player_image, junk_texture_list, game_music, game_sounds);
+    prevent_quit();
 
     loop { 
 
+        if is_quit_requested() {
+            main_game.quit();
+        }
+
         main_game.run();
 
         next_frame().await
 
     }
-}
+}
\ No newline at end of file
@@ -0,0 +1,281 @@
+use macroquad::{prelude::*, rand::gen_range, miniquad::{BlendFactor, BlendState, BlendValue, Equation, PipelineParams}};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+// Particles alive at once. New ones are dropped while the pool is full.
+const MAX_PARTICLES: usize = 512;
+
+// How particles are born and how they change over their life.
+pub struct EmitterDef {
+    // Particles per second, for emitters that run continuously.
+    pub rate: f32,
+    // Seconds, picked at random between the two.
+    pub lifetime: [f32; 2],
+    // Pixels per second, picked at random between the two.
+    pub speed: [f32; 2],
+    // Radians, with 0 pointing right and a quarter turn pointing down the screen.
+    pub direction: f32,
+    // Half the angle of the cone particles leave in.
+    pub spread: f32,
+    pub start_color: Color,
+    pub end_color: Color,
+    pub start_size: f32,
+    pub end_size: f32,
+    // Pixels per second squared, pulling down the screen.
+    pub gravity: f32,
+    // Additive particles brighten what is behind them, for anything that glows.
+    pub additive: bool,
+}
+
+pub static SPARKLE: EmitterDef = EmitterDef {
+    rate: 0.0,
+    lifetime: [0.3, 0.6],
+    speed: [60.0, 180.0],
+    direction: 0.0,
+    spread: PI,
+    start_color: Color::new(1.0, 0.9, 0.5, 1.0),
+    end_color: Color::new(1.0, 0.6, 0.1, 0.0),
+    start_size: 6.0,
+    end_size: 1.0,
+    gravity: 0.0,
+    additive: true,
+};
+
+pub static DEBRIS: EmitterDef = EmitterDef {
+    rate: 0.0,
+    lifetime: [0.4, 0.8],
+    speed: [80.0, 260.0],
+    direction: 0.0,
+    spread: PI,
+    start_color: Color::new(0.6, 0.55, 0.5, 1.0),
+    end_color: Color::new(0.35, 0.3, 0.3, 0.0),
+    start_size: 7.0,
+    end_size: 3.0,
+    gravity: 200.0,
+    additive: false,
+};
+
+pub static EXHAUST: EmitterDef = EmitterDef {
+    rate: 60.0,
+    lifetime: [0.2, 0.35],
+    speed: [120.0, 200.0],
+    direction: FRAC_PI_2,
+    spread: 0.25,
+    start_color: Color::new(1.0, 0.75, 0.3, 0.9),
+    end_color: Color::new(0.9, 0.2, 0.1, 0.0),
+    start_size: 8.0,
+    end_size: 2.0,
+    gravity: 0.0,
+    additive: true,
+};
+
+struct Particle {
+    def: &'static EmitterDef,
+    position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+}
+
+// A continuous source of particles, carrying over the fraction of a particle
+// that did not fit into the last frame.
+pub struct Emitter {
+    pub def: &'static EmitterDef,
+    accumulator: f32,
+}
+
+impl Emitter {
+    pub fn new(def: &'static EmitterDef) -> Self {
+        Self { def, accumulator: 0.0 }
+    }
+}
+
+const VERTEX: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}"#;
+
+const FRAGMENT: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+
+void main() {
+    gl_FragColor = color * texture2D(Texture, uv);
+}"#;
+
+fn additive_material() -> Option<Material> {
+    let params = MaterialParams {
+        pipeline_params: PipelineParams {
+            color_blend: Some(BlendState::new(Equation::Add, BlendFactor::Value(BlendValue::SourceAlpha), BlendFactor::One)),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    match load_material(VERTEX, FRAGMENT, params) {
+        Ok(material) => Some(material),
+        Err(error) => {
+            println!("Could not load additive particle material, using normal blending: {:?}", error);
+            None
+        },
+    }
+}
+
+// Every particle in the game, in one pool that is allocated up front.
+// Purely cosmetic, so it runs on frame time and its own random numbers.
+pub struct Particles {
+    particles: Vec<Particle>,
+    // Share of particles actually spawned, from the particle density setting.
+    pub density: f32,
+    additive: Option<Material>,
+}
+
+impl Particles {
+    pub fn new(density: f32) -> Self {
+        Self { particles: Vec::with_capacity(MAX_PARTICLES), density, additive: additive_material() }
+    }
+
+    fn spawn(&mut self, def: &'static EmitterDef, position: Vec2, direction: f32) {
+        if self.particles.len() >= MAX_PARTICLES {
+            return;
+        }
+        let angle = direction + gen_range(-def.spread, def.spread);
+        let speed = gen_range(def.speed[0], def.speed[1]);
+        let velocity = vec2(angle.cos(), angle.sin()) * speed;
+        self.particles.push(Particle { def, position, velocity, age: 0.0, lifetime: gen_range(def.lifetime[0], def.lifetime[1]) });
+    }
+
+    // `count` particles at once, thinned out by the density setting.
+    pub fn burst(&mut self, def: &'static EmitterDef, position: Vec2, count: usize) {
+        let count = (count as f32 * self.density).round() as usize;
+        for _ in 0..count {
+            self.spawn(def, position, def.direction);
+        }
+    }
+
+    // Keeps an emitter going for `dt` seconds, aimed at `direction`. `throttle` scales its rate.
+    pub fn emit(&mut self, emitter: &mut Emitter, position: Vec2, direction: f32, throttle: f32, dt: f32) {
+        emitter.accumulator += emitter.def.rate * throttle * self.density * dt;
+        while emitter.accumulator >= 1.0 {
+            emitter.accumulator -= 1.0;
+            self.spawn(emitter.def, position, direction);
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let mut index = 0;
+        while index < self.particles.len() {
+            let particle = &mut self.particles[index];
+            particle.age += dt;
+            if particle.age >= particle.lifetime {
+                self.particles.swap_remove(index);
+                continue;
+            }
+            particle.velocity.y += particle.def.gravity * dt;
+            particle.position += particle.velocity * dt;
+            index += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    fn draw_pass(&self, additive: bool) {
+        for particle in self.particles.iter().filter(|particle| particle.def.additive == additive) {
+            let def = particle.def;
+            let life = particle.age / particle.lifetime;
+            let color = Color::new(
+                def.start_color.r + (def.end_color.r - def.start_color.r) * life,
+                def.start_color.g + (def.end_color.g - def.start_color.g) * life,
+                def.start_color.b + (def.end_color.b - def.start_color.b) * life,
+                def.start_color.a + (def.end_color.a - def.start_color.a) * life,
+            );
+            let size = def.start_size + (def.end_size - def.start_size) * life;
+            draw_rectangle(particle.position.x - size / 2.0, particle.position.y - size / 2.0, size, size, color);
+        }
+    }
+
+    pub fn draw(&self) {
+        self.draw_pass(false);
+        match self.additive {
+            Some(material) => {
+                gl_use_material(material);
+                self.draw_pass(true);
+                gl_use_default_material();
+            },
+            None => self.draw_pass(true),
+        }
+    }
+}
+
+// Exhaust leans away from the way the ship is moving, like a real thruster trail.
+pub fn exhaust_direction(motion_x: f32) -> f32 {
+    if motion_x > 0.0 {
+        FRAC_PI_2 + 0.5
+    }
+    else if motion_x < 0.0 {
+        FRAC_PI_2 - 0.5
+    }
+    else {
+        FRAC_PI_2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Skips the additive material, which needs a window to load.
+    fn particles(density: f32) -> Particles {
+        Particles { particles: Vec::with_capacity(MAX_PARTICLES), density, additive: None }
+    }
+
+    #[test]
+    fn full_pool_drops_new_particles() {
+        let mut particles = particles(1.0);
+        particles.burst(&SPARKLE, Vec2::ZERO, MAX_PARTICLES + 100);
+        let mut exhaust = Emitter::new(&EXHAUST);
+        particles.emit(&mut exhaust, Vec2::ZERO, FRAC_PI_2, 1.0, 10.0);
+        assert_eq!(particles.particles.len(), MAX_PARTICLES);
+        assert_eq!(particles.particles.capacity(), MAX_PARTICLES);
+        particles.update(1.0);
+        assert!(particles.particles.is_empty());
+    }
+
+    #[test]
+    fn zero_density_emits_nothing() {
+        let mut particles = particles(0.0);
+        particles.burst(&DEBRIS, Vec2::ZERO, 40);
+        let mut exhaust = Emitter::new(&EXHAUST);
+        for _ in 0..60 {
+            particles.emit(&mut exhaust, Vec2::ZERO, FRAC_PI_2, 1.0, 1.0 / 60.0);
+        }
+        assert!(particles.particles.is_empty());
+    }
+
+    #[test]
+    fn emitter_carries_partial_particles_over() {
+        let mut particles = particles(0.5);
+        let mut exhaust = Emitter::new(&EXHAUST);
+        // 60 per second at half density is one particle every other sixtieth of a second.
+        let mut counts = Vec::new();
+        for _ in 0..4 {
+            particles.emit(&mut exhaust, Vec2::ZERO, FRAC_PI_2, 1.0, 1.0 / 60.0);
+            counts.push(particles.particles.len());
+        }
+        assert_eq!(counts, vec![0, 1, 1, 2]);
+    }
+}
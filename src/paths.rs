@@ -0,0 +1,122 @@
+use std::{env, fs, path::{Path, PathBuf}};
+
+const APP_DIR: &str = "space_cleanup";
+const APP_NAME: &str = "SpaceCleanup";
+const ANDROID_PACKAGE: &str = "com.anatoliyk.space_cleanup";
+pub const DIR_ENV_VAR: &str = "SPACE_CLEANUP_DIR";
+pub const DIR_FLAG: &str = "--data-dir";
+pub const REPLAY_FLAG: &str = "--replay";
+
+pub struct Paths {
+    pub data_dir: PathBuf,
+    pub config_dir: PathBuf,
+    pub log_dir: PathBuf,
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+fn xdg_dir(variable: &str, fallback: &str) -> PathBuf {
+    match env::var_os(variable).filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join(APP_DIR),
+        None => match home_dir() {
+            Some(home) => home.join(fallback).join(APP_DIR),
+            None => PathBuf::from("data"),
+        },
+    }
+}
+
+// Reads `--flag value` or `--flag=value` from the command line.
+fn flag_value(flag: &str) -> Option<PathBuf> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(value) = arg.strip_prefix(flag).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(PathBuf::from(value));
+        }
+    }
+    None
+}
+
+fn override_dir() -> Option<PathBuf> {
+    flag_value(DIR_FLAG).or_else(|| env::var_os(DIR_ENV_VAR).filter(|dir| !dir.is_empty()).map(PathBuf::from))
+}
+
+// A replay to play straight away, e.g. one attached to a bug report.
+pub fn replay_arg() -> Option<PathBuf> {
+    flag_value(REPLAY_FLAG)
+}
+
+impl Paths {
+    pub fn resolve() -> Self {
+        if let Some(dir) = override_dir() {
+            return Self::in_dir(&dir);
+        }
+        if cfg!(target_os = "android") {
+            Self::in_dir(&Path::new("/data/data").join(ANDROID_PACKAGE).join("files"))
+        }
+        else if cfg!(target_os = "macos") {
+            match home_dir() {
+                Some(home) => Self::in_dir(&home.join("Library/Application Support").join(APP_NAME)),
+                None => Self::in_dir(Path::new("data")),
+            }
+        }
+        else if cfg!(target_os = "windows") {
+            match env::var_os("APPDATA") {
+                Some(app_data) => Self::in_dir(&PathBuf::from(app_data).join(APP_NAME)),
+                None => Self::in_dir(Path::new("data")),
+            }
+        }
+        else {
+            let data_dir = xdg_dir("XDG_DATA_HOME", ".local/share");
+            Self {
+                log_dir: xdg_dir("XDG_STATE_HOME", ".local/state").join("logs"),
+                config_dir: xdg_dir("XDG_CONFIG_HOME", ".config"),
+                data_dir,
+            }
+        }
+    }
+
+    pub fn in_dir(dir: &Path) -> Self {
+        Self { data_dir: dir.to_path_buf(), config_dir: dir.to_path_buf(), log_dir: dir.join("logs") }
+    }
+
+    pub fn create_dirs(&self) {
+        for dir in [&self.data_dir, &self.config_dir, &self.log_dir] {
+            if let Err(error) = fs::create_dir_all(dir) {
+                println!("Could not create {}: {}", dir.display(), error);
+            }
+        }
+    }
+
+    pub fn save_file(&self) -> PathBuf {
+        self.data_dir.join("player.json")
+    }
+
+    pub fn settings_file(&self) -> PathBuf {
+        self.config_dir.join("settings.json")
+    }
+
+    pub fn replay_dir(&self) -> PathBuf {
+        self.data_dir.join("replays")
+    }
+
+    // The most recent run is always kept here, ready to be watched from the Game Over screen.
+    pub fn last_replay_file(&self) -> PathBuf {
+        self.replay_dir().join("last.json")
+    }
+}
+
+// Bundled assets live next to the executable in development builds and in
+// `Contents/Resources` inside a macOS release bundle.
+pub fn resource(path: &str) -> String {
+    if cfg!(all(target_os = "macos", not(debug_assertions))) {
+        format!("../Resources/{}", path)
+    }
+    else {
+        path.to_string()
+    }
+}
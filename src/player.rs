@@ -1,113 +1,132 @@
-use macroquad::prelude::{Rect, Texture2D, screen_height, screen_width};
-use crate::{object::Object, game};
-use serde::{Deserialize, Serialize};
-use serde_json;
-use std::{fs::{File, create_dir}, io::{Write, Read}};
+use macroquad::prelude::{Rect, Texture2D, Vec2};
+use crate::{object::Object, save::SaveData, collision::{Shape, ShapeScratch}, content::PlayerDef, effects::Effects, shop::Loadout};
 
 pub struct Player {
     position: Rect,
     pub health: i32,
     pub points: i32,
     pub coins: i32,
+    pub shields: u32,
+    // The upgrades this run started with.
+    pub loadout: Loadout,
+    // Power-ups picked up this run.
+    pub effects: Effects,
+    // Whether hits pass straight through right now, and for how many seconds every
+    // hit grants that. The simulation schedules when it runs out.
+    pub invulnerable: bool,
+    invulnerability: f64,
     pub texture: Texture2D,
+    pub shape: Shape,
+    previous: Vec2,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Vector2D {
-    x: f32,
-    y: f32,
-}
+impl Player {
+    pub fn get_coins(&self) -> i32 {
+        self.coins.clone()
+    }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct PlayerJson {
-    position: Vector2D,
-    coins: i32,
-}
+    // A shield soaks up a whole hit. Power-up shields go first since they run out anyway.
+    // Returns whether the hit got through to the hull.
+    pub fn hurt(&mut self, damage: i32) -> bool {
+        self.invulnerable = self.invulnerability > 0.0;
+        if damage > 0 && self.effects.absorb_hit() {
+            return false;
+        }
+        if damage > 0 && self.shields > 0 {
+            self.shields -= 1;
+            return false;
+        }
+        self.health -= damage;
+        true
+    }
 
-impl PlayerJson {
-    fn new(x_pos: f32, y_pos: f32, coins: i32) -> Self {
-        Self { position: Vector2D { x: x_pos, y: y_pos }, coins: coins }
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable
     }
-}
 
-impl Player {
-    pub fn get_coins(&self) -> i32 {
-        self.coins.clone()
+    pub fn invulnerability(&self) -> f64 {
+        self.invulnerability
     }
 
-    pub fn hurt(&mut self, damage: i32) {
-        self.health += damage;
+    // Returns the points actually awarded after the score multipliers.
+    pub fn collect_point(&mut self, points: i32) -> i32 {
+        let awarded = (points as f32 * self.loadout.score_multiplier * self.effects.score_multiplier()).round() as i32;
+        self.points += awarded;
+        awarded
     }
 
-    pub fn collect_point(&mut self, points: i32) {
-        self.points += points;
+    pub fn apply_def(&mut self, def: &PlayerDef) {
+        self.position.w = def.size[0];
+        self.position.h = def.size[1];
+        self.shape = def.collision.clone();
+        self.invulnerability = def.invulnerability;
     }
 
-    pub fn save_player(&self) {
-        let x_pos = self.get_x();
-        let y_pos = self.get_y();
-        let coins = self.get_coins();
-        let player_json = PlayerJson::new( x_pos, y_pos, coins );
-        let mut player_data = String::new();
-        match serde_json::to_string(&player_json) {
-            Ok(data) => {
-                player_data = data.clone();
-            },
-            Err(error) => {
-                println!("An Error has occured: {}", error);
-                game::exit_game();
-            },
-        }
-        match create_dir("data") {
-            Ok(_) => {
-                println!("Data directory created");
-            },
-            Err(error) => {
-                println!("{}", error);
-            }
-        }
-        let json_file_init = File::create("data/player.json");
-        let mut json_file: File;
-        match json_file_init {
-            Ok(file) => {
-                json_file = file;
-                println!("Saved data");
-            },
-            Err(error) => {
-                println!("Cannot Create File {}", error);
-                game::exit_game();
-            }
-        }
-        match json_file.write_all(player_data.as_bytes()) {
-            Ok(_) => {/*println!("File Written!")*/},
-            Err(error) => println!("Error {}", error),
-        }
+    // Puts the ship back where a new run starts, with a fresh score and the loadout's health and shields.
+    pub fn reset(&mut self, world: Vec2, loadout: Loadout) {
+        self.loadout = loadout;
+        self.points = 0;
+        self.health = loadout.max_health;
+        self.shields = loadout.shields;
+        self.effects.clear();
+        self.invulnerable = false;
+        self.position.x = world.x / 2.0;
+        self.position.y = world.y / 10.0 * 7.5;
+        self.begin_tick();
+    }
+
+    pub fn begin_tick(&mut self) {
+        self.previous = self.position.point();
+    }
+
+    pub fn draw_rect(&self, alpha: f32) -> Rect {
+        let point = self.previous.lerp(self.position.point(), alpha);
+        Rect { x: point.x, y: point.y, w: self.position.w, h: self.position.h }
     }
 
-    pub fn load_player(&mut self, texture: Texture2D, path: String) -> Player {
-        let mut file = File::open(path).expect("Failed to open file");
+    pub fn previous_shape<'a>(&self, scratch: &'a mut ShapeScratch) -> &'a Shape {
+        scratch.place(&self.shape, self.draw_rect(0.0), 0.0)
+    }
+
+    pub fn motion(&self) -> Vec2 {
+        self.position.point() - self.previous
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.position.center()
+    }
 
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).expect("Failed to read file");
+    pub fn apply_save(&mut self, data: &SaveData) {
+        self.coins = data.coins;
+    }
 
-        let data: PlayerJson = serde_json::from_str(&contents).expect("Failed to deserialize JSON");
-        Player { position: Rect { x: data.position.x, y: data.position.x, w: 75.0, h: 125.0 }, health: 5, points: 0, coins: data.coins, texture: texture }
+    pub fn write_save(&self, data: &mut SaveData) {
+        data.coins = self.get_coins();
     }
 }
 
 impl Object for Player {
-    fn new(texure: Texture2D) -> Self {
+    fn new(texure: Texture2D, world: Vec2) -> Self {
+        let x = world.x / 2.0;
+        let y = world.y / 10.0 * 7.5;
         Self {
             position: Rect {
-                x: screen_width() / 2.0,
-                y: screen_height() / 10.0 * 7.5,
+                x: x,
+                y: y,
                 w: 75.0,
                 h: 125.0
             },
             health: 5,
             points: 0,
             coins: 0,
-            texture: texure
+            shields: 0,
+            loadout: Loadout::default(),
+            effects: Effects::default(),
+            invulnerable: false,
+            invulnerability: 1.0,
+            texture: texure,
+            shape: Shape::rect(Rect { x: 0.0, y: 0.0, w: 75.0, h: 125.0 }),
+            previous: Vec2::new(x, y)
         }
     }
     fn get_x(&self) -> f32 {
@@ -122,13 +141,7 @@ impl Object for Player {
     fn get_height(&self) -> f32 {
         self.position.h.clone()
     }
-    fn get_rect(&self) -> Rect {
-        self.position.clone()
-    }
     fn move_x(&mut self, speed: f32) {
         self.position.x += speed;
     }
-    fn move_y(&mut self, speed: f32) {
-        self.position.y += speed;
-    }
 }
\ No newline at end of file
@@ -0,0 +1,221 @@
+use macroquad::prelude::{Vec2, vec2};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+use crate::{difficulty::Preset, save::{self, SaveError}, shop::Loadout, simulation::InputSnapshot};
+
+pub const REPLAY_VERSION: u32 = 1;
+
+const LEFT: u8 = 1;
+const RIGHT: u8 = 2;
+
+// What kind of run was recorded, so it can be set up the same way again.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunMode {
+    Endless(Preset),
+    Level(String),
+}
+
+// `count` ticks in a row with the same input: key flags and touch positions.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+struct Frame(u32, u8, Vec<f32>);
+
+impl Frame {
+    fn new(input: &InputSnapshot) -> Self {
+        let mut keys = 0;
+        if input.left {
+            keys |= LEFT;
+        }
+        if input.right {
+            keys |= RIGHT;
+        }
+        Frame(1, keys, input.touches.clone())
+    }
+
+    fn input(&self) -> InputSnapshot {
+        InputSnapshot { left: self.1 & LEFT != 0, right: self.1 & RIGHT != 0, touches: self.2.clone() }
+    }
+}
+
+// Everything needed to play a run back tick for tick: how it started and the
+// input of every tick, run-length encoded so idle stretches cost nothing.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub mode: RunMode,
+    pub tick_rate: u32,
+    pub world: [f32; 2],
+    // Recordings from before the shop existed had no upgrades.
+    #[serde(default)]
+    pub loadout: Loadout,
+    frames: Vec<Frame>,
+    // Window size changes as (tick, new size), applied before that tick runs.
+    resizes: Vec<(u64, [f32; 2])>,
+    // How many ticks `frames` add up to, counted while recording and on load.
+    #[serde(skip)]
+    ticks: u64,
+}
+
+impl Replay {
+    pub fn new(seed: u64, mode: RunMode, tick_rate: u32, world: Vec2, loadout: Loadout) -> Self {
+        Self { version: REPLAY_VERSION, seed, mode, tick_rate, world: [world.x, world.y], loadout, frames: Vec::new(), resizes: Vec::new(), ticks: 0 }
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn record(&mut self, input: &InputSnapshot) {
+        let frame = Frame::new(input);
+        match self.frames.last_mut() {
+            Some(last) if last.1 == frame.1 && last.2 == frame.2 => last.0 += 1,
+            _ => self.frames.push(frame),
+        }
+        self.ticks += 1;
+    }
+
+    pub fn record_resize(&mut self, world: Vec2) {
+        let size = [world.x, world.y];
+        // Same as the last change, or as the starting size before any change.
+        if self.resizes.last().is_some_and(|(_, last)| *last == size) || (self.resizes.is_empty() && self.world == size) {
+            return;
+        }
+        self.resizes.push((self.ticks, size));
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, SaveError> {
+        let contents = fs::read_to_string(path)?;
+        let mut replay: Replay = serde_json::from_str(&contents)?;
+        if replay.version > REPLAY_VERSION {
+            return Err(SaveError::UnsupportedVersion(replay.version));
+        }
+        replay.ticks = replay.frames.iter().map(|frame| frame.0 as u64).sum();
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SaveError> {
+        let contents = serde_json::to_string(self)?;
+        save::write_atomic(path, &contents)
+    }
+}
+
+// One tick's worth of recorded input, plus a new world size if the window was resized.
+pub struct ReplayTick {
+    pub input: InputSnapshot,
+    pub resize: Option<Vec2>,
+}
+
+// Walks a replay one tick at a time. `speed` is how many ticks run per tick of
+// real time, and `step` runs a single tick while paused.
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    pub paused: bool,
+    pub speed: u32,
+    pub step: bool,
+    frame: usize,
+    used: u32,
+    tick: u64,
+    resize: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, paused: false, speed: 1, step: false, frame: 0, used: 0, tick: 0, resize: 0 }
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.replay.frames.len()
+    }
+
+    pub fn next(&mut self) -> Option<ReplayTick> {
+        let frame = self.replay.frames.get(self.frame)?;
+        let input = frame.input();
+        self.used += 1;
+        if self.used >= frame.0 {
+            self.frame += 1;
+            self.used = 0;
+        }
+        let resize = match self.replay.resizes.get(self.resize) {
+            Some((tick, [x, y])) if *tick <= self.tick => {
+                self.resize += 1;
+                Some(vec2(*x, *y))
+            },
+            _ => None,
+        };
+        self.tick += 1;
+        Some(ReplayTick { input, resize })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macroquad::prelude::Texture2D;
+    use crate::{content::test_content, object::Object, player::Player, simulation::{SimEvent, Simulation}};
+
+    const ARCHETYPES: &str = r#"[
+        { "name": "scrap", "category": "scrap", "texture": "scrap.png", "size": [40, 40], "points": [5, 15] },
+        { "name": "asteroid", "category": "asteroid", "texture": "asteroid.png", "size": [60, 60], "damage": 1 }
+    ]"#;
+    const DT: f32 = 1.0 / 60.0;
+
+    fn simulation(world: Vec2, seed: u64) -> Simulation {
+        let player = Player::new(Texture2D::empty(), world);
+        let mut simulation = Simulation::new(world, seed, player, test_content(ARCHETYPES), Preset::Normal);
+        simulation.restart();
+        simulation
+    }
+
+    #[test]
+    fn replay_plays_back_the_recorded_run() {
+        let world = vec2(800.0, 600.0);
+        let mut live = simulation(world, 9);
+        let mut replay = Replay::new(9, RunMode::Endless(Preset::Normal), 60, world, live.loadout);
+        let mut recorded: Vec<SimEvent> = Vec::new();
+        for tick in 0..1200 {
+            let input = InputSnapshot { left: tick % 100 < 40, right: tick % 100 > 70, touches: if tick % 250 < 20 { vec![100.0] } else { Vec::new() } };
+            let size = if tick < 600 { world } else { vec2(640.0, 480.0) };
+            live.resize(size);
+            replay.record_resize(size);
+            replay.record(&input);
+            recorded.extend(live.step(&input, DT));
+        }
+        assert_eq!(replay.ticks(), 1200);
+        assert!(recorded.len() > 10);
+
+        let loaded: Replay = serde_json::from_str(&serde_json::to_string(&replay).unwrap()).unwrap();
+        let mut played = simulation(vec2(loaded.world[0], loaded.world[1]), loaded.seed);
+        let mut player = ReplayPlayer::new(loaded);
+        let mut events = Vec::new();
+        while let Some(tick) = player.next() {
+            if let Some(size) = tick.resize {
+                played.resize(size);
+            }
+            events.extend(played.step(&tick.input, DT));
+        }
+        assert!(player.is_finished());
+        assert_eq!(player.tick(), 1200);
+        assert_eq!(events, recorded);
+        assert_eq!(played.player.points, live.player.points);
+        assert_eq!(played.player.get_x(), live.player.get_x());
+    }
+
+    #[test]
+    fn only_changed_sizes_are_recorded() {
+        let mut replay = Replay::new(1, RunMode::Endless(Preset::Normal), 60, vec2(800.0, 600.0), Loadout::default());
+        replay.record_resize(vec2(800.0, 600.0));
+        replay.record(&InputSnapshot::default());
+        replay.record_resize(vec2(640.0, 480.0));
+        replay.record(&InputSnapshot::default());
+        replay.record_resize(vec2(640.0, 480.0));
+        replay.record(&InputSnapshot { left: true, ..Default::default() });
+        assert_eq!(replay.resizes, vec![(1, [640.0, 480.0])]);
+        assert_eq!(replay.frames.len(), 2);
+        assert_eq!(replay.ticks(), 3);
+    }
+}
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::{levels::LevelRecords, leaderboard::{LifetimeStats, ScoreEntry}, shop::Upgrades};
+use std::{fmt, fs::{self, File}, io::Write, path::{Path, PathBuf}};
+
+pub const SAVE_VERSION: u32 = 5;
+// Saves used to be written relative to the working directory.
+pub const LEGACY_SAVE_PATH: &str = "data/player.json";
+
+// Each entry upgrades a save from version `index` to `index + 1`.
+const MIGRATIONS: [fn(Value) -> Value; SAVE_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub coins: i32,
+    // Best result for every level that has been beaten, by level name.
+    pub levels: LevelRecords,
+    // Best endless runs, best first.
+    pub leaderboard: Vec<ScoreEntry>,
+    pub lifetime: LifetimeStats,
+    pub upgrades: Upgrades,
+    // Skins bought with coins, by name.
+    pub owned_skins: Vec<String>,
+    // Name of the equipped skin; empty means the default.
+    pub skin: String,
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        Self { version: SAVE_VERSION, coins: 0, levels: LevelRecords::new(), leaderboard: Vec::new(), lifetime: LifetimeStats::default(), upgrades: Upgrades::default(), owned_skins: Vec::new(), skin: String::new() }
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Corrupt(serde_json::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "could not access save file: {}", error),
+            SaveError::Corrupt(error) => write!(f, "save file is corrupt: {}", error),
+            SaveError::UnsupportedVersion(version) => write!(f, "save file version {} is newer than supported version {}", version, SAVE_VERSION),
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(error: std::io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(error: serde_json::Error) -> Self {
+        SaveError::Corrupt(error)
+    }
+}
+
+// The original layout was `{ "position": { "x", "y" }, "coins" }` with no version field.
+fn migrate_v0_to_v1(value: Value) -> Value {
+    let coins = value.get("coins").cloned().unwrap_or(json!(0));
+    json!({ "version": 1, "coins": coins })
+}
+
+// Version 2 added level progression.
+fn migrate_v1_to_v2(mut value: Value) -> Value {
+    value["version"] = json!(2);
+    value["levels"] = json!({});
+    value
+}
+
+// Version 3 added the leaderboard and lifetime stats.
+fn migrate_v2_to_v3(mut value: Value) -> Value {
+    value["version"] = json!(3);
+    value["leaderboard"] = json!([]);
+    value["lifetime"] = json!({ "runs": 0, "playtime": 0.0, "points": 0, "scraps": 0, "asteroids_hit": 0 });
+    value
+}
+
+// Version 4 added shop upgrades.
+fn migrate_v3_to_v4(mut value: Value) -> Value {
+    value["version"] = json!(4);
+    value["upgrades"] = json!({});
+    value
+}
+
+// Version 5 added skins.
+fn migrate_v4_to_v5(mut value: Value) -> Value {
+    value["version"] = json!(5);
+    value["owned_skins"] = json!([]);
+    value["skin"] = json!("");
+    value
+}
+
+fn migrate(mut value: Value) -> Result<SaveData, SaveError> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if version > SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        value = migration(value);
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(extension);
+    path.with_file_name(name)
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    sibling_path(path, ".bak")
+}
+
+pub fn load(path: &Path) -> Result<SaveData, SaveError> {
+    let contents = fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&contents)?;
+    migrate(value)
+}
+
+// Falls back to the backup and then to a fresh save, so a bad file never stops the game from starting.
+pub fn load_or_default(path: &Path) -> SaveData {
+    match load(path) {
+        Ok(data) => return data,
+        Err(SaveError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound => {
+            return SaveData::default();
+        },
+        Err(error) => println!("Warning: {}, trying backup", error),
+    }
+    match load(&backup_path(path)) {
+        Ok(data) => data,
+        Err(error) => {
+            println!("Warning: backup save unusable ({}), starting fresh", error);
+            SaveData::default()
+        },
+    }
+}
+
+// Writes to a temporary file first and renames it over the old file, so a crash
+// mid-write leaves either the old or the new contents on disk, never a torn file.
+pub fn write_atomic(path: &Path, contents: &str) -> Result<(), SaveError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = sibling_path(path, ".tmp");
+    let mut temp_file = File::create(&temp_path)?;
+    temp_file.write_all(contents.as_bytes())?;
+    temp_file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+pub fn save(path: &Path, data: &SaveData) -> Result<(), SaveError> {
+    let contents = serde_json::to_string(data)?;
+    if load(path).is_ok() {
+        fs::copy(path, backup_path(path))?;
+    }
+    write_atomic(path, &contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory per test, so tests running in parallel never share files.
+    fn temp_save_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("space_cleanup_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("player.json")
+    }
+
+    #[test]
+    fn version_0_save_migrates_to_current() {
+        let value: Value = serde_json::from_str(r#"{ "position": { "x": 120.0, "y": 450.0 }, "coins": 42 }"#).unwrap();
+        let data = migrate(value).unwrap();
+        assert_eq!(data.version, SAVE_VERSION);
+        assert_eq!(data.coins, 42);
+        assert!(data.levels.is_empty());
+        assert!(data.leaderboard.is_empty());
+        assert_eq!(data.lifetime.runs, 0);
+        assert!(data.owned_skins.is_empty());
+        assert!(data.skin.is_empty());
+    }
+
+    #[test]
+    fn newer_save_is_rejected() {
+        let value = json!({ "version": SAVE_VERSION + 1, "coins": 1 });
+        assert!(matches!(migrate(value), Err(SaveError::UnsupportedVersion(version)) if version == SAVE_VERSION + 1));
+    }
+
+    #[test]
+    fn corrupt_save_falls_back_to_backup() {
+        let path = temp_save_path("backup");
+        let mut data = SaveData { coins: 10, ..Default::default() };
+        save(&path, &data).unwrap();
+        // The second save moves the first one into the backup.
+        data.coins = 20;
+        save(&path, &data).unwrap();
+        write_atomic(&path, "{ \"version\": 5, \"coi").unwrap();
+        assert!(matches!(load(&path), Err(SaveError::Corrupt(_))));
+        assert_eq!(load_or_default(&path).coins, 10);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn missing_save_starts_fresh() {
+        let path = temp_save_path("missing");
+        assert_eq!(load_or_default(&path).coins, 0);
+    }
+}
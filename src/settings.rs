@@ -0,0 +1,226 @@
+use macroquad::prelude::KeyCode;
+use serde::{Deserialize, Serialize};
+use std::{fs, ops::RangeInclusive, path::Path};
+use crate::{save::{self, SaveError}, difficulty::Preset};
+
+// Keys that can be picked in the Options screen, stored in the settings file by their `Debug` name.
+const BINDABLE_KEYS: [KeyCode; 44] = [
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+    KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+    KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+    KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down,
+    KeyCode::Space, KeyCode::Enter, KeyCode::Escape, KeyCode::Tab,
+];
+
+pub const LANGUAGES: [(&str, &str); 1] = [("en", "English")];
+pub const UI_SCALE_RANGE: RangeInclusive<f32> = 0.5..=2.0;
+pub const WINDOW_WIDTH_RANGE: RangeInclusive<i32> = 640..=3840;
+pub const WINDOW_HEIGHT_RANGE: RangeInclusive<i32> = 360..=2160;
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+pub fn key_code(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.iter().copied().find(|key| key_name(*key) == name)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Pause,
+    Save,
+    Debug,
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [Action::MoveLeft, Action::MoveRight, Action::Pause, Action::Save, Action::Debug];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::Pause => "Pause",
+            Action::Save => "Save",
+            Action::Debug => "Debug Overlay",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub move_left: String,
+    pub move_right: String,
+    pub pause: String,
+    pub save: String,
+    pub debug: String,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            move_left: key_name(KeyCode::A),
+            move_right: key_name(KeyCode::D),
+            pause: key_name(KeyCode::P),
+            save: key_name(KeyCode::B),
+            debug: key_name(KeyCode::G),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn name(&self, action: Action) -> &str {
+        match action {
+            Action::MoveLeft => &self.move_left,
+            Action::MoveRight => &self.move_right,
+            Action::Pause => &self.pause,
+            Action::Save => &self.save,
+            Action::Debug => &self.debug,
+        }
+    }
+
+    // Falls back to the default key when the settings file names a key we don't know.
+    pub fn key(&self, action: Action) -> KeyCode {
+        match key_code(self.name(action)) {
+            Some(key) => key,
+            None => key_code(KeyBindings::default().name(action)).unwrap_or(KeyCode::Unknown),
+        }
+    }
+
+    // Only keys in `BINDABLE_KEYS` can be bound, so the settings file can always name them.
+    // Returns whether the key was taken.
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> bool {
+        if !BINDABLE_KEYS.contains(&key) {
+            return false;
+        }
+        let name = key_name(key);
+        match action {
+            Action::MoveLeft => self.move_left = name,
+            Action::MoveRight => self.move_right = name,
+            Action::Pause => self.pause = name,
+            Action::Save => self.save = name,
+            Action::Debug => self.debug = name,
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Accessibility {
+    pub reduce_motion: bool,
+    pub large_text: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub music_volume: f32,
+    pub sound_volume: f32,
+    pub key_bindings: KeyBindings,
+    pub fullscreen: bool,
+    pub window_width: i32,
+    pub window_height: i32,
+    pub ui_scale: f32,
+    pub language: String,
+    pub accessibility: Accessibility,
+    pub autosave_interval: f64,
+    pub tick_rate: u32,
+    pub difficulty: Preset,
+    pub rumble: bool,
+    // Share of particles spawned, lower for slow devices.
+    pub particle_density: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            music_volume: 25.0,
+            sound_volume: 25.0,
+            key_bindings: KeyBindings::default(),
+            fullscreen: false,
+            window_width: 1280,
+            window_height: 720,
+            ui_scale: 1.0,
+            language: String::from("en"),
+            accessibility: Accessibility::default(),
+            autosave_interval: crate::autosave::DEFAULT_INTERVAL,
+            tick_rate: 120,
+            difficulty: Preset::Normal,
+            rumble: true,
+            particle_density: if cfg!(target_os = "android") { 0.5 } else { 1.0 },
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(contents) => match serde_json::from_str::<Settings>(&contents) {
+                Ok(settings) => settings.clamped(),
+                Err(error) => {
+                    println!("Warning: settings file is corrupt ({}), using defaults", error);
+                    Settings::default()
+                },
+            },
+            Err(_) => Settings::default(),
+        }
+    }
+
+    // Keeps hand-edited values inside what the Options screen allows.
+    pub fn clamped(mut self) -> Self {
+        self.ui_scale = if self.ui_scale.is_finite() { self.ui_scale.clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end()) } else { 1.0 };
+        self.window_width = self.window_width.clamp(*WINDOW_WIDTH_RANGE.start(), *WINDOW_WIDTH_RANGE.end());
+        self.window_height = self.window_height.clamp(*WINDOW_HEIGHT_RANGE.start(), *WINDOW_HEIGHT_RANGE.end());
+        self
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SaveError> {
+        let contents = serde_json::to_string_pretty(self)?;
+        save::write_atomic(path, &contents)
+    }
+
+    pub fn text_size(&self) -> f32 {
+        if self.accessibility.large_text {
+            35.0
+        }
+        else {
+            25.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_bindable_keys_are_bound() {
+        let mut bindings = KeyBindings::default();
+        assert!(!bindings.bind(Action::Pause, KeyCode::F1));
+        assert_eq!(bindings.name(Action::Pause), "P");
+        assert!(bindings.bind(Action::Pause, KeyCode::Escape));
+        assert_eq!(bindings.key(Action::Pause), KeyCode::Escape);
+    }
+
+    #[test]
+    fn unknown_key_names_fall_back_to_defaults() {
+        let bindings = KeyBindings { move_left: String::from("F1"), ..Default::default() };
+        assert_eq!(bindings.key(Action::MoveLeft), KeyCode::A);
+    }
+
+    #[test]
+    fn loaded_sizes_are_clamped() {
+        let settings: Settings = serde_json::from_str(r#"{ "ui_scale": 40.0, "window_width": 10, "window_height": 100000 }"#).unwrap();
+        let settings = settings.clamped();
+        assert_eq!(settings.ui_scale, 2.0);
+        assert_eq!(settings.window_width, 640);
+        assert_eq!(settings.window_height, 2160);
+        assert_eq!(Settings { ui_scale: f32::NAN, ..Default::default() }.clamped().ui_scale, 1.0);
+    }
+}
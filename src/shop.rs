@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use crate::{content::{Skin, Unlock}, save::SaveData};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Upgrade {
+    MaxHealth,
+    MoveSpeed,
+    MagnetRadius,
+    ShieldCharges,
+    ScoreMultiplier,
+    Cannon,
+}
+
+impl Upgrade {
+    pub const ALL: [Upgrade; 6] = [Upgrade::MaxHealth, Upgrade::MoveSpeed, Upgrade::MagnetRadius, Upgrade::ShieldCharges, Upgrade::ScoreMultiplier, Upgrade::Cannon];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Upgrade::MaxHealth => "Hull Plating",
+            Upgrade::MoveSpeed => "Thrusters",
+            Upgrade::MagnetRadius => "Scrap Magnet",
+            Upgrade::ShieldCharges => "Shield Generator",
+            Upgrade::ScoreMultiplier => "Salvage License",
+            Upgrade::Cannon => "Pulse Cannon",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Upgrade::MaxHealth => "+1 starting health per tier",
+            Upgrade::MoveSpeed => "+10% movement speed per tier",
+            Upgrade::MagnetRadius => "Pulls in nearby scrap",
+            Upgrade::ShieldCharges => "Blocks one asteroid hit per charge, every run",
+            Upgrade::ScoreMultiplier => "More points from every scrap",
+            Upgrade::Cannon => "Fires at asteroids on its own, faster per tier",
+        }
+    }
+
+    // Coins for each tier, in order. The number of prices is the number of tiers.
+    pub fn prices(&self) -> &'static [i32] {
+        match self {
+            Upgrade::MaxHealth => &[50, 150, 400],
+            Upgrade::MoveSpeed => &[40, 120, 300],
+            Upgrade::MagnetRadius => &[60, 180, 450],
+            Upgrade::ShieldCharges => &[75, 200, 500],
+            Upgrade::ScoreMultiplier => &[100, 300, 800],
+            Upgrade::Cannon => &[150, 350, 750],
+        }
+    }
+}
+
+// Tiers bought of each upgrade, stored in the save file.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Upgrades {
+    pub max_health: u8,
+    pub move_speed: u8,
+    pub magnet_radius: u8,
+    pub shield_charges: u8,
+    pub score_multiplier: u8,
+    pub cannon: u8,
+}
+
+impl Upgrades {
+    pub fn tier(&self, upgrade: Upgrade) -> u8 {
+        match upgrade {
+            Upgrade::MaxHealth => self.max_health,
+            Upgrade::MoveSpeed => self.move_speed,
+            Upgrade::MagnetRadius => self.magnet_radius,
+            Upgrade::ShieldCharges => self.shield_charges,
+            Upgrade::ScoreMultiplier => self.score_multiplier,
+            Upgrade::Cannon => self.cannon,
+        }
+    }
+
+    fn tier_mut(&mut self, upgrade: Upgrade) -> &mut u8 {
+        match upgrade {
+            Upgrade::MaxHealth => &mut self.max_health,
+            Upgrade::MoveSpeed => &mut self.move_speed,
+            Upgrade::MagnetRadius => &mut self.magnet_radius,
+            Upgrade::ShieldCharges => &mut self.shield_charges,
+            Upgrade::ScoreMultiplier => &mut self.score_multiplier,
+            Upgrade::Cannon => &mut self.cannon,
+        }
+    }
+
+    // Price of the next tier, or `None` once it is maxed out.
+    pub fn next_price(&self, upgrade: Upgrade) -> Option<i32> {
+        upgrade.prices().get(self.tier(upgrade) as usize).copied()
+    }
+
+    pub fn buy(&mut self, upgrade: Upgrade, coins: &mut i32) -> bool {
+        match self.next_price(upgrade) {
+            Some(price) if *coins >= price => {
+                *coins -= price;
+                *self.tier_mut(upgrade) += 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    pub fn loadout(&self) -> Loadout {
+        let base = Loadout::default();
+        Loadout {
+            max_health: base.max_health + self.max_health as i32,
+            speed_multiplier: 1.0 + 0.1 * self.move_speed as f32,
+            magnet_radius: [0.0, 80.0, 140.0, 200.0][self.magnet_radius.min(3) as usize],
+            shields: self.shield_charges as u32,
+            score_multiplier: [1.0, 1.25, 1.5, 2.0][self.score_multiplier.min(3) as usize],
+            fire_interval: [0.0, 1.2, 0.8, 0.5][self.cannon.min(3) as usize],
+        }
+    }
+}
+
+// What the upgrades add up to, handed to the player at the start of every run.
+// Replays store it too, so runs with upgrades play back the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Loadout {
+    pub max_health: i32,
+    pub speed_multiplier: f32,
+    // Scraps closer than this to the ship drift towards it.
+    pub magnet_radius: f32,
+    pub shields: u32,
+    pub score_multiplier: f32,
+    // Seconds between cannon shots; 0 without a cannon.
+    pub fire_interval: f32,
+}
+
+impl Default for Loadout {
+    fn default() -> Self {
+        Self { max_health: 5, speed_multiplier: 1.0, magnet_radius: 0.0, shields: 0, score_multiplier: 1.0, fire_interval: 0.0 }
+    }
+}
+
+pub fn unlock_met(unlock: &Unlock, save: &SaveData) -> bool {
+    match unlock {
+        Unlock::HighScore { points } => save.leaderboard.first().is_some_and(|best| best.score >= *points),
+        Unlock::LifetimeScraps { count } => save.lifetime.scraps >= *count,
+        Unlock::Runs { count } => save.lifetime.runs >= *count,
+        Unlock::Level { name } => save.levels.contains_key(name),
+    }
+}
+
+fn unlocked(skin: &Skin, save: &SaveData) -> bool {
+    skin.unlock.as_ref().is_none_or(|unlock| unlock_met(unlock, save))
+}
+
+// Bought skins are remembered by name; free ones are owned as soon as they are unlocked.
+pub fn skin_owned(skin: &Skin, save: &SaveData) -> bool {
+    save.owned_skins.contains(&skin.name) || (skin.price == 0 && unlocked(skin, save))
+}
+
+pub fn skin_for_sale(skin: &Skin, save: &SaveData) -> bool {
+    !skin_owned(skin, save) && skin.price > 0 && unlocked(skin, save)
+}
+
+pub fn buy_skin(skin: &Skin, save: &mut SaveData, coins: &mut i32) -> bool {
+    if !skin_for_sale(skin, save) || *coins < skin.price {
+        return false;
+    }
+    *coins -= skin.price;
+    save.owned_skins.push(skin.name.clone());
+    true
+}
+
+// The equipped skin, falling back to the first one if it is gone from the content file or no longer owned.
+pub fn selected_skin(skins: &[Skin], save: &SaveData) -> usize {
+    skins.iter().position(|skin| skin.name == save.skin && skin_owned(skin, save)).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skin(name: &str, price: i32, unlock: Option<Unlock>) -> Skin {
+        Skin { name: name.to_string(), texture: format!("{}.png", name), trail: None, price, unlock }
+    }
+
+    #[test]
+    fn upgrades_cost_more_each_tier_until_maxed_out() {
+        let mut upgrades = Upgrades::default();
+        let mut coins = 600;
+        assert_eq!(upgrades.next_price(Upgrade::MaxHealth), Some(50));
+        assert!(upgrades.buy(Upgrade::MaxHealth, &mut coins));
+        assert_eq!(coins, 550);
+        assert_eq!(upgrades.next_price(Upgrade::MaxHealth), Some(150));
+        assert!(upgrades.buy(Upgrade::MaxHealth, &mut coins));
+        assert!(upgrades.buy(Upgrade::MaxHealth, &mut coins));
+        assert_eq!((upgrades.tier(Upgrade::MaxHealth), coins), (3, 0));
+        assert_eq!(upgrades.next_price(Upgrade::MaxHealth), None);
+
+        let mut coins = 1000;
+        assert!(!upgrades.buy(Upgrade::MaxHealth, &mut coins));
+        assert_eq!(coins, 1000);
+    }
+
+    #[test]
+    fn upgrades_need_enough_coins() {
+        let mut upgrades = Upgrades::default();
+        let mut coins = 149;
+        assert!(!upgrades.buy(Upgrade::Cannon, &mut coins));
+        assert_eq!((upgrades.tier(Upgrade::Cannon), coins), (0, 149));
+        coins += 1;
+        assert!(upgrades.buy(Upgrade::Cannon, &mut coins));
+        assert_eq!((upgrades.tier(Upgrade::Cannon), coins), (1, 0));
+    }
+
+    #[test]
+    fn loadout_adds_up_the_tiers() {
+        assert_eq!(Upgrades::default().loadout(), Loadout::default());
+        let upgrades = Upgrades { max_health: 2, move_speed: 3, magnet_radius: 1, shield_charges: 2, score_multiplier: 3, cannon: 2 };
+        let loadout = upgrades.loadout();
+        assert_eq!(loadout.max_health, 7);
+        assert!((loadout.speed_multiplier - 1.3).abs() < 1e-6);
+        assert_eq!(loadout.magnet_radius, 80.0);
+        assert_eq!(loadout.shields, 2);
+        assert_eq!(loadout.score_multiplier, 2.0);
+        assert_eq!(loadout.fire_interval, 0.8);
+    }
+
+    #[test]
+    fn skins_are_owned_bought_or_locked() {
+        let mut save = SaveData::default();
+        let free = skin("free", 0, None);
+        let priced = skin("priced", 100, None);
+        let veteran = skin("veteran", 0, Some(Unlock::Runs { count: 3 }));
+        let locked = skin("locked", 50, Some(Unlock::Runs { count: 3 }));
+
+        assert!(skin_owned(&free, &save) && !skin_for_sale(&free, &save));
+        assert!(!skin_owned(&priced, &save) && skin_for_sale(&priced, &save));
+        assert!(!skin_owned(&veteran, &save) && !skin_for_sale(&veteran, &save));
+        assert!(!skin_owned(&locked, &save) && !skin_for_sale(&locked, &save));
+
+        let mut coins = 120;
+        assert!(!buy_skin(&locked, &mut save, &mut coins));
+        assert!(!buy_skin(&free, &mut save, &mut coins));
+        assert!(buy_skin(&priced, &mut save, &mut coins));
+        assert_eq!(coins, 20);
+        assert!(skin_owned(&priced, &save) && !skin_for_sale(&priced, &save));
+        assert!(!buy_skin(&priced, &mut save, &mut coins));
+
+        save.lifetime.runs = 3;
+        assert!(skin_owned(&veteran, &save));
+        assert!(skin_for_sale(&locked, &save));
+        assert!(!buy_skin(&locked, &mut save, &mut coins));
+        assert_eq!(coins, 20);
+        assert_eq!(save.owned_skins, vec!["priced".to_string()]);
+    }
+}
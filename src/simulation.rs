@@ -0,0 +1,718 @@
+use macroquad::{prelude::{Vec2, vec2}, rand::RandGenerator};
+use chrono::{Datelike, Local};
+use crate::{combo::Combo, player::Player, space_objects::SpaceObject, object::Object, content::{Content, Category}, effects::EffectKind, timer::{Clock, ManualClock, Scheduler}, collision::{Shape, ShapeScratch}, difficulty::{Difficulty, Preset, Progress}, director::{Director, Arena, Spawn, FIRST_WAVE_DELAY}, levels::{Level, LevelRun}, shop::Loadout, weapon::{Cannon, BOLT_DAMAGE}};
+
+// Everything the simulation needs to know about the player's input for one step.
+// `touches` holds the x coordinate of every active touch.
+#[derive(Clone, Debug, Default)]
+pub struct InputSnapshot {
+    pub left: bool,
+    pub right: bool,
+    pub touches: Vec<f32>,
+}
+
+// Counts kept over a single run for the leaderboard.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RunStats {
+    pub scraps: u32,
+    pub asteroids_hit: u32,
+    pub asteroids_destroyed: u32,
+    pub best_combo: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SimEvent {
+    // `points` already includes `combo`, the multiplier the pickup scored with.
+    Pickup { points: i32, combo: u32, position: Vec2, archetype: usize },
+    // `shielded` hits were soaked up without losing health.
+    Hit { damage: i32, position: Vec2, archetype: usize, shielded: bool },
+    PowerUp { effect: EffectKind, position: Vec2, archetype: usize },
+    // An asteroid shot to pieces, centered on `position`.
+    Destroyed { points: i32, position: Vec2, archetype: usize },
+    LevelComplete { stars: u8 },
+    GameOver,
+}
+
+// How fast the scrap magnet pulls, relative to the current fall speed.
+const MAGNET_PULL: f32 = 1.5;
+
+// How many objects of each category can be in play at once. The pools are
+// filled up front so spawning never allocates.
+const POOL_SIZE: usize = 64;
+const POWER_UP_POOL_SIZE: usize = 8;
+
+// Sideways speed of the outermost fragments when an asteroid breaks up.
+const FRAGMENT_DRIFT: f32 = 80.0;
+
+// What the simulation schedules on its own clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Cue {
+    // The director plans its next wave.
+    Wave,
+    // The cannon fires, every `fire_interval` of the loadout.
+    Fire,
+    // The ship's post-hit invulnerability runs out.
+    Vulnerable,
+}
+
+// A fresh seed for a normal run.
+pub fn random_seed() -> u64 {
+    (macroquad::miniquad::date::now() * 1000.0) as u64
+}
+
+// Everyone playing on the same local date gets the same run, e.g. 20261018.
+pub fn daily_seed() -> u64 {
+    let today = Local::now().date_naive();
+    today.year() as u64 * 10000 + today.month() as u64 * 100 + today.day() as u64
+}
+
+fn fill_pool(category: Category, size: usize, content: &Content, rng: &RandGenerator) -> Vec<SpaceObject> {
+    (0..size).map(|_| {
+        let index = content.pick(category, rng);
+        SpaceObject::new(index, &content.archetypes[index])
+    }).collect()
+}
+
+// Tests the whole path the object took this tick, measured relative to the player,
+// so nothing can pass through the ship between two ticks.
+fn swept_hit(object: &SpaceObject, player_shape: &Shape, player_motion: Vec2, scratch: &mut ShapeScratch) -> bool {
+    object.previous_shape(scratch).sweep(object.motion() - player_motion, player_shape).is_some()
+}
+
+pub struct Simulation {
+    pub player: Player,
+    pub scraps: Vec<SpaceObject>,
+    pub asteroids: Vec<SpaceObject>,
+    pub power_ups: Vec<SpaceObject>,
+    pub cannon: Cannon,
+    pub world: Vec2,
+    pub game_over: bool,
+    pub content: Content,
+    pub difficulty: Difficulty,
+    // The preset endless runs use. Levels bring their own difficulty.
+    pub preset: Preset,
+    pub director: Director,
+    // The level being played, or `None` for an endless run.
+    pub level: Option<LevelRun>,
+    pub stats: RunStats,
+    pub combo: Combo,
+    // Upgrades the next run starts with. Changing it mid-run waits for a restart.
+    pub loadout: Loadout,
+    pub clock: ManualClock,
+    schedule: Scheduler<Cue>,
+    // Every random roll in a run comes from `rng`, which is reseeded with this on
+    // restart, so the same seed and inputs always play out the same way.
+    pub seed: u64,
+    rng: RandGenerator,
+    // Where the ship and the object being tested are placed in the world each tick.
+    player_scratch: ShapeScratch,
+    object_scratch: ShapeScratch,
+}
+
+impl Simulation {
+    pub fn new(
+        world: Vec2,
+        seed: u64,
+        player: Player,
+        content: Content,
+        preset: Preset
+    ) -> Self
+    {
+        let rng = RandGenerator::new();
+        rng.srand(seed);
+        let scraps = fill_pool(Category::Scrap, POOL_SIZE, &content, &rng);
+        let asteroids = fill_pool(Category::Asteroid, POOL_SIZE, &content, &rng);
+        let power_ups = fill_pool(Category::PowerUp, POWER_UP_POOL_SIZE, &content, &rng);
+        let clock = ManualClock::new(0.0);
+        let difficulty = content.difficulty.get(preset).clone();
+        let mut schedule = Scheduler::new();
+        schedule.after(FIRST_WAVE_DELAY, Cue::Wave, &clock);
+        Self { player, scraps, asteroids, power_ups, cannon: Cannon::new(), world, game_over: false, content, difficulty, preset, director: Director::new(), level: None, stats: RunStats::default(), combo: Combo::default(), loadout: Loadout::default(), clock, schedule, seed, rng, player_scratch: ShapeScratch::default(), object_scratch: ShapeScratch::default() }
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress { score: self.player.points as f32, time: self.clock.now() as f32 }
+    }
+
+    // Seconds left of the ship's post-hit invulnerability.
+    pub fn invulnerable_for(&self) -> f64 {
+        self.schedule.remaining(&Cue::Vulnerable, &self.clock).unwrap_or(0.0)
+    }
+
+    // Holds everything scheduled while the game's pause menu is open.
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused {
+            self.schedule.pause(&self.clock);
+        }
+        else {
+            self.schedule.resume(&self.clock);
+        }
+    }
+
+    pub fn start_endless(&mut self, preset: Preset, seed: u64) {
+        self.difficulty = self.content.difficulty.get(preset).clone();
+        self.preset = preset;
+        self.seed = seed;
+        self.level = None;
+        self.restart();
+    }
+
+    pub fn start_level(&mut self, index: usize, level: &Level) {
+        self.difficulty = level.difficulty();
+        self.level = Some(LevelRun::new(index, level));
+        self.restart();
+    }
+
+    pub fn resize(&mut self, world: Vec2) {
+        self.world = world;
+    }
+
+    pub fn restart(&mut self) {
+        self.game_over = false;
+        self.stats = RunStats::default();
+        self.combo.reset();
+        self.clock = ManualClock::new(0.0);
+        self.rng.srand(self.seed);
+        self.director.reset();
+        if let Some(run) = self.level.as_mut() {
+            run.reset();
+        }
+        self.player.reset(self.world, self.loadout);
+        self.cannon.reset();
+        self.schedule.clear();
+        // Levels script their own spawns instead of the director's waves.
+        if self.level.is_none() {
+            self.schedule.after(FIRST_WAVE_DELAY, Cue::Wave, &self.clock);
+        }
+        if self.loadout.fire_interval > 0.0 {
+            self.schedule.every(self.loadout.fire_interval as f64, Cue::Fire, &self.clock);
+        }
+        for object in self.scraps.iter_mut().chain(self.asteroids.iter_mut()).chain(self.power_ups.iter_mut()) {
+            object.despawn();
+        }
+    }
+
+    fn arena(&self) -> Arena {
+        let (object_width, object_reach) = self.content.archetypes.iter()
+            .filter(|archetype| archetype.category == Category::Asteroid)
+            .fold((0.0, 0.0), |(width, reach): (f32, f32), archetype| {
+                (width.max(archetype.size[0]), reach.max(archetype.size[0].hypot(archetype.size[1]) / 2.0))
+            });
+        let asteroids = self.asteroids.iter()
+            .filter(|asteroid| asteroid.active)
+            .map(|asteroid| asteroid.fall_column(self.world))
+            .collect();
+        Arena { world: self.world, ship_width: self.player.get_width(), ship_x: self.player.center().x, object_width, object_reach, asteroids }
+    }
+
+    // Wakes a sleeping object from the pool. When every object is already in
+    // play the spawn is skipped.
+    fn spawn_object(&mut self, index: usize, x: f32) -> Option<&mut SpaceObject> {
+        let archetype = &self.content.archetypes[index];
+        let pool = match archetype.category {
+            Category::Scrap => &mut self.scraps,
+            Category::Asteroid => &mut self.asteroids,
+            Category::PowerUp => &mut self.power_ups,
+        };
+        let object = pool.iter_mut().find(|object| !object.active)?;
+        object.spawn(index, archetype, x, self.world, &self.rng);
+        Some(object)
+    }
+
+    // Breaks a shot-down asteroid into its fragments, spread out side by side and flying apart.
+    // Fragments that would fly into the director's safe lane fall straight down instead,
+    // or are left out when even that would block it.
+    fn destroy_asteroid(&mut self, slot: usize) -> SimEvent {
+        let asteroid = &mut self.asteroids[slot];
+        asteroid.despawn();
+        let (archetype, center, points) = (asteroid.archetype, asteroid.center(), asteroid.get_points());
+        let points = self.player.collect_point(points);
+        self.stats.asteroids_destroyed += 1;
+        let fragments = self.content.archetypes[archetype].fragments.as_ref().map(|fragments| (fragments.archetype_index, fragments.count));
+        if let Some((index, count)) = fragments {
+            let width = self.content.archetypes[index].size[0];
+            let middle = (count - 1) as f32 / 2.0;
+            let band = self.director.band();
+            for piece in 0..count {
+                let spread = piece as f32 - middle;
+                let world = self.world;
+                if let Some(fragment) = self.spawn_object(index, center.x) {
+                    fragment.place(center + vec2(spread * width, 0.0), world);
+                    fragment.drift = spread / middle.max(1.0) * FRAGMENT_DRIFT;
+                    if band.is_some_and(|band| band.blocks(fragment.fall_column(world))) {
+                        fragment.drift = 0.0;
+                    }
+                    if band.is_some_and(|band| band.blocks(fragment.fall_column(world))) {
+                        fragment.despawn();
+                    }
+                }
+            }
+        }
+        SimEvent::Destroyed { points, position: center, archetype }
+    }
+
+    fn spawn(&mut self, spawn: Spawn) {
+        let mut category = spawn.category;
+        if category == Category::Scrap && self.content.power_up_chance > 0.0 && self.rng.gen_range(0.0, 1.0) < self.content.power_up_chance {
+            category = Category::PowerUp;
+        }
+        let index = self.content.pick(category, &self.rng);
+        self.spawn_object(index, spawn.x);
+    }
+
+    pub fn step(&mut self, input: &InputSnapshot, dt: f32) -> Vec<SimEvent> {
+        let mut events = Vec::new();
+        if self.game_over {
+            return events;
+        }
+        if self.player.health <= 0 {
+            self.game_over = true;
+            events.push(SimEvent::GameOver);
+            return events;
+        }
+
+        self.clock.advance(dt as f64);
+        self.player.begin_tick();
+        self.player.effects.tick(dt as f64);
+        self.combo.update(self.clock.now());
+        for object in self.scraps.iter_mut().chain(self.asteroids.iter_mut()).chain(self.power_ups.iter_mut()) {
+            object.begin_tick();
+        }
+
+        let progress = self.progress();
+        let mut fire = false;
+        for cue in self.schedule.poll(&self.clock) {
+            match cue {
+                Cue::Wave => {
+                    let arena = self.arena();
+                    let gap = self.director.plan_wave(&self.difficulty, progress, &arena, &self.rng, &self.clock);
+                    self.schedule.after(gap, Cue::Wave, &self.clock);
+                },
+                Cue::Fire => fire = true,
+                Cue::Vulnerable => self.player.invulnerable = false,
+            }
+        }
+        if self.level.is_some() {
+            let now = self.clock.now();
+            while let Some(spawn) = self.level.as_mut().and_then(|run| run.next_spawn(now)) {
+                self.spawn_object(spawn.archetype, spawn.x * self.world.x);
+            }
+        }
+        else {
+            while let Some(spawn) = self.director.next_spawn(&self.clock) {
+                self.spawn(spawn);
+            }
+        }
+
+        let speed = self.difficulty.player_speed.value(progress) * self.player.loadout.speed_multiplier;
+        let fall_speed = self.difficulty.fall_speed.value(progress) * self.player.effects.fall_speed_factor();
+        if input.left && self.player.get_x() > 0.0 {
+            self.player.move_x(-(speed * dt));
+        }
+        if input.right && self.player.get_x() < self.world.x - self.player.get_width() {
+            self.player.move_x(speed * dt);
+        }
+        for touch_x in input.touches.iter() {
+            if *touch_x > self.player.get_x() {
+                self.player.move_x(speed * dt);
+            }
+            else if *touch_x < self.player.get_x() {
+                self.player.move_x(-(speed * dt));
+            }
+        }
+
+        let player_shape = self.player.previous_shape(&mut self.player_scratch);
+        let player_motion = self.player.motion();
+        let ship_center = self.player.center();
+        let magnet_radius = self.player.loadout.magnet_radius.max(self.player.effects.magnet_radius());
+        for scrap in self.scraps.iter_mut().filter(|scrap| scrap.active) {
+            if scrap.position.y > self.world.y {
+                scrap.despawn();
+                continue;
+            }
+            scrap.move_y(fall_speed * scrap.fall_speed * dt);
+            if magnet_radius > 0.0 {
+                scrap.pull_towards(ship_center, magnet_radius, fall_speed * MAGNET_PULL * dt);
+            }
+            scrap.spin(dt);
+            if swept_hit(scrap, player_shape, player_motion, &mut self.object_scratch) {
+                let combo = self.combo.pickup(self.clock.now());
+                let points = self.player.collect_point(scrap.get_points() * combo as i32);
+                events.push(SimEvent::Pickup { points, combo, position: scrap.center(), archetype: scrap.archetype });
+                self.stats.scraps += 1;
+                self.stats.best_combo = self.stats.best_combo.max(self.combo.count);
+                scrap.despawn();
+            }
+        }
+        for asteroid in self.asteroids.iter_mut().filter(|asteroid| asteroid.active) {
+            if asteroid.is_off_screen(self.world) {
+                asteroid.despawn();
+                continue;
+            }
+            asteroid.move_y(fall_speed * asteroid.fall_speed * dt);
+            asteroid.apply_drift(dt);
+            asteroid.spin(dt);
+            // Asteroids pass straight through the ship while it is invulnerable.
+            if !self.player.is_invulnerable() && swept_hit(asteroid, player_shape, player_motion, &mut self.object_scratch) {
+                let shielded = !self.player.hurt(asteroid.get_damage());
+                if self.player.is_invulnerable() {
+                    self.schedule.after(self.player.invulnerability(), Cue::Vulnerable, &self.clock);
+                }
+                // Any hit ends the combo, even one a shield soaked up.
+                self.combo.reset();
+                events.push(SimEvent::Hit { damage: asteroid.get_damage(), position: asteroid.center(), archetype: asteroid.archetype, shielded });
+                self.stats.asteroids_hit += 1;
+                asteroid.despawn();
+            }
+        }
+
+        // Each bolt stops at the first asteroid along its path this tick.
+        let muzzle = vec2(self.player.center().x, self.player.get_y());
+        self.cannon.update(dt);
+        if fire {
+            self.cannon.fire(muzzle, dt);
+        }
+        for bolt in self.cannon.bolts.iter_mut().filter(|bolt| bolt.active) {
+            let bolt_shape = bolt.previous_shape();
+            let mut target: Option<(usize, f32)> = None;
+            for (slot, asteroid) in self.asteroids.iter().enumerate().filter(|(_, asteroid)| asteroid.active) {
+                if let Some(time) = bolt_shape.sweep(bolt.motion() - asteroid.motion(), asteroid.previous_shape(&mut self.object_scratch)) {
+                    if target.is_none_or(|(_, first)| time < first) {
+                        target = Some((slot, time));
+                    }
+                }
+            }
+            if let Some((slot, _)) = target {
+                bolt.active = false;
+                self.asteroids[slot].take_hit(BOLT_DAMAGE);
+            }
+        }
+        // Broken up once the bolts are done, since fragments can take over pool slots.
+        for slot in 0..self.asteroids.len() {
+            if self.asteroids[slot].active && self.asteroids[slot].is_destroyed() {
+                let event = self.destroy_asteroid(slot);
+                events.push(event);
+            }
+        }
+
+        // Placed again since breaking up asteroids needed the whole simulation.
+        let player_shape = self.player.previous_shape(&mut self.player_scratch);
+        for power_up in self.power_ups.iter_mut().filter(|power_up| power_up.active) {
+            if power_up.position.y > self.world.y {
+                power_up.despawn();
+                continue;
+            }
+            power_up.move_y(fall_speed * power_up.fall_speed * dt);
+            power_up.spin(dt);
+            if swept_hit(power_up, player_shape, player_motion, &mut self.object_scratch) {
+                let archetype = &self.content.archetypes[power_up.archetype];
+                if let Some(def) = archetype.effect.and_then(|kind| self.content.effect(kind)) {
+                    self.player.effects.apply(def);
+                    events.push(SimEvent::PowerUp { effect: def.kind, position: power_up.center(), archetype: power_up.archetype });
+                }
+                power_up.despawn();
+            }
+        }
+
+        let cleared = !self.scraps.iter().chain(self.asteroids.iter()).chain(self.power_ups.iter()).any(|object| object.active);
+        if let Some(run) = self.level.as_mut() {
+            let points = self.player.points;
+            let time = self.clock.now();
+            if run.is_won(points, time) {
+                let stars = run.level.rating(points, time);
+                run.result = Some(stars);
+                self.game_over = true;
+                events.push(SimEvent::LevelComplete { stars });
+            }
+            else if run.is_lost(cleared) {
+                self.game_over = true;
+                events.push(SimEvent::GameOver);
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macroquad::prelude::Texture2D;
+    use crate::content::test_content;
+
+    const ARCHETYPES: &str = r#"[
+        { "name": "scrap", "category": "scrap", "texture": "scrap.png", "size": [40, 40], "points": [10, 10] },
+        { "name": "asteroid", "category": "asteroid", "texture": "asteroid.png", "size": [60, 60], "damage": 1 }
+    ]"#;
+    const SCRAP: usize = 0;
+    const ASTEROID: usize = 1;
+    const DT: f32 = 1.0 / 60.0;
+
+    fn simulation() -> Simulation {
+        let world = vec2(800.0, 600.0);
+        let player = Player::new(Texture2D::empty(), world);
+        let mut simulation = Simulation::new(world, 1, player, test_content(ARCHETYPES), Preset::Normal);
+        simulation.restart();
+        simulation
+    }
+
+    // Puts an object a little above the ship so it falls onto it within a few ticks.
+    fn drop_on_player(simulation: &mut Simulation, index: usize) {
+        let center = simulation.player.center() - vec2(0.0, 100.0);
+        let world = simulation.world;
+        simulation.spawn_object(index, center.x).unwrap().place(center, world);
+    }
+
+    // Steps for half a second, before the director sends its first wave.
+    fn run(simulation: &mut Simulation, input: &InputSnapshot) -> Vec<SimEvent> {
+        let mut events = Vec::new();
+        for _ in 0..30 {
+            events.extend(simulation.step(input, DT));
+        }
+        events
+    }
+
+    #[test]
+    fn scrap_falling_on_the_ship_is_picked_up() {
+        let mut simulation = simulation();
+        drop_on_player(&mut simulation, SCRAP);
+        let events = run(&mut simulation, &InputSnapshot::default());
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], SimEvent::Pickup { points: 10, combo: 1, archetype: SCRAP, .. }));
+        assert_eq!(simulation.player.points, 10);
+        assert_eq!(simulation.stats.scraps, 1);
+        assert!((simulation.clock.now() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn asteroid_hits_the_ship_once() {
+        let mut simulation = simulation();
+        drop_on_player(&mut simulation, ASTEROID);
+        let events = run(&mut simulation, &InputSnapshot::default());
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], SimEvent::Hit { damage: 1, archetype: ASTEROID, shielded: false, .. }));
+        assert_eq!(simulation.player.health, 4);
+        assert_eq!(simulation.stats.asteroids_hit, 1);
+    }
+
+    #[test]
+    fn invulnerability_wears_off_on_schedule() {
+        let mut simulation = simulation();
+        drop_on_player(&mut simulation, ASTEROID);
+        run(&mut simulation, &InputSnapshot::default());
+        assert!(simulation.player.is_invulnerable());
+        let left = simulation.invulnerable_for();
+        assert!(left > 0.0 && left < simulation.player.invulnerability());
+        for _ in 0..=(left / DT as f64).ceil() as usize {
+            simulation.step(&InputSnapshot::default(), DT);
+        }
+        assert!(!simulation.player.is_invulnerable());
+        assert_eq!(simulation.invulnerable_for(), 0.0);
+    }
+
+    #[test]
+    fn overlapping_asteroids_cost_one_health() {
+        let mut simulation = simulation();
+        drop_on_player(&mut simulation, ASTEROID);
+        drop_on_player(&mut simulation, ASTEROID);
+        let events = run(&mut simulation, &InputSnapshot::default());
+        let hits: Vec<&SimEvent> = events.iter().filter(|event| matches!(event, SimEvent::Hit { .. })).collect();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(simulation.player.health, 4);
+        assert!(simulation.player.is_invulnerable());
+    }
+
+    #[test]
+    fn losing_the_last_health_ends_the_run() {
+        let mut simulation = simulation();
+        simulation.player.health = 1;
+        drop_on_player(&mut simulation, ASTEROID);
+        let events = run(&mut simulation, &InputSnapshot::default());
+        assert_eq!(events.iter().filter(|event| **event == SimEvent::GameOver).count(), 1);
+        assert!(simulation.game_over);
+        assert!(simulation.step(&InputSnapshot::default(), DT).is_empty());
+    }
+
+    #[test]
+    fn ship_speeds_up_with_score() {
+        let mut simulation = simulation();
+        let input = InputSnapshot { right: true, ..Default::default() };
+        // The test presets keep the original `points * 2 + 250` formula.
+        for points in [0, 100, 200, 300] {
+            simulation.restart();
+            simulation.player.points = points;
+            let x = simulation.player.get_x();
+            simulation.step(&input, 0.1);
+            let speed = (simulation.player.get_x() - x) / 0.1;
+            assert!((speed - (250.0 + points as f32 * 2.0)).abs() < 0.01, "{} points moved at {}", points, speed);
+        }
+    }
+
+    #[test]
+    fn shielded_hit_still_ends_the_combo() {
+        let mut simulation = simulation();
+        simulation.player.shields = 1;
+        for _ in 0..3 {
+            drop_on_player(&mut simulation, SCRAP);
+            run(&mut simulation, &InputSnapshot::default());
+        }
+        assert_eq!(simulation.combo.count, 3);
+        drop_on_player(&mut simulation, ASTEROID);
+        let events = run(&mut simulation, &InputSnapshot::default());
+        assert!(matches!(events[0], SimEvent::Hit { shielded: true, .. }));
+        assert_eq!(simulation.player.health, 5);
+        assert_eq!(simulation.combo.count, 0);
+        assert_eq!(simulation.stats.best_combo, 3);
+    }
+
+    // A rock that takes three bolts and breaks into three pebbles.
+    const ARMORED: &str = r#"[
+        { "name": "scrap", "category": "scrap", "texture": "scrap.png", "size": [40, 40] },
+        { "name": "rock", "category": "asteroid", "texture": "rock.png", "size": [60, 60], "damage": 1, "points": [7, 7], "hit_points": 3, "fragments": { "archetype": "pebble", "count": 3 } },
+        { "name": "pebble", "category": "asteroid", "texture": "pebble.png", "size": [30, 30], "damage": 1, "spawn_weight": 0 }
+    ]"#;
+    const ROCK: usize = 1;
+    const PEBBLE: usize = 2;
+
+    fn armored(fire_interval: f32) -> Simulation {
+        let world = vec2(800.0, 600.0);
+        let player = Player::new(Texture2D::empty(), world);
+        let mut simulation = Simulation::new(world, 1, player, test_content(ARMORED), Preset::Normal);
+        simulation.loadout.fire_interval = fire_interval;
+        simulation.restart();
+        simulation
+    }
+
+    fn place(simulation: &mut Simulation, index: usize, center: Vec2) {
+        let world = simulation.world;
+        simulation.spawn_object(index, center.x).unwrap().place(center, world);
+    }
+
+    #[test]
+    fn asteroids_break_after_their_hit_points() {
+        let mut simulation = armored(0.0);
+        place(&mut simulation, ROCK, vec2(200.0, 100.0));
+        for _ in 0..2 {
+            simulation.asteroids[0].take_hit(BOLT_DAMAGE);
+            assert!(simulation.step(&InputSnapshot::default(), DT).is_empty());
+            assert!(simulation.asteroids[0].active);
+        }
+        simulation.asteroids[0].take_hit(BOLT_DAMAGE);
+        let events = simulation.step(&InputSnapshot::default(), DT);
+        assert!(matches!(events[..], [SimEvent::Destroyed { points: 7, archetype: ROCK, .. }]));
+        assert_eq!(simulation.player.points, 7);
+        assert_eq!(simulation.stats.asteroids_destroyed, 1);
+    }
+
+    #[test]
+    fn broken_asteroids_scatter_into_fragments() {
+        let mut simulation = armored(0.0);
+        let center = vec2(200.0, 100.0);
+        place(&mut simulation, ROCK, center);
+        simulation.asteroids[0].health = 1;
+        simulation.asteroids[0].take_hit(BOLT_DAMAGE);
+        simulation.step(&InputSnapshot::default(), DT);
+        let mut fragments: Vec<(f32, f32)> = simulation.asteroids.iter()
+            .filter(|asteroid| asteroid.active)
+            .inspect(|fragment| assert_eq!(fragment.archetype, PEBBLE))
+            .map(|fragment| (fragment.center().x - center.x, fragment.drift))
+            .collect();
+        fragments.sort_by(|a, b| a.0.total_cmp(&b.0));
+        assert_eq!(fragments, vec![(-30.0, -FRAGMENT_DRIFT), (0.0, 0.0), (30.0, FRAGMENT_DRIFT)]);
+    }
+
+    #[test]
+    fn bolts_hit_the_nearest_asteroid_in_their_way() {
+        let mut simulation = armored(0.1);
+        let muzzle = vec2(simulation.player.center().x, simulation.player.get_y());
+        // The far rock takes the earlier pool slot, so only the sweep times pick the near one.
+        place(&mut simulation, ROCK, muzzle - vec2(0.0, 180.0));
+        place(&mut simulation, ROCK, muzzle - vec2(0.0, 100.0));
+        // One long tick fires a bolt that passes both rocks.
+        simulation.step(&InputSnapshot::default(), 0.25);
+        assert_eq!(simulation.asteroids[0].health, 3);
+        assert_eq!(simulation.asteroids[1].health, 3 - BOLT_DAMAGE);
+        assert!(simulation.cannon.bolts.iter().all(|bolt| !bolt.active));
+    }
+
+    #[test]
+    fn cannon_fires_once_every_interval() {
+        let mut simulation = armored(0.5);
+        // Bolts only fly up, so a new shot is the lowest bolt being lower than before.
+        let lowest = |simulation: &Simulation| simulation.cannon.bolts.iter().filter(|bolt| bolt.active).map(|bolt| bolt.position.y).fold(f32::MIN, f32::max);
+        let mut shots = Vec::new();
+        for tick in 1..=100 {
+            let before = lowest(&simulation);
+            simulation.step(&InputSnapshot::default(), DT);
+            if lowest(&simulation) > before {
+                shots.push(tick);
+            }
+        }
+        // The first shot comes one interval in, not at the start of the run.
+        assert_eq!(shots.len(), 3, "fired on ticks {:?}", shots);
+        for (shot, tick) in shots.iter().enumerate() {
+            assert!((tick - 30 * (shot as i32 + 1)).abs() <= 1, "fired on ticks {:?}", shots);
+        }
+    }
+
+    #[test]
+    fn ship_following_the_lane_is_never_hit() {
+        // Pointless scraps keep the ship's speed the same, and rocks break into faster, drifting pebbles.
+        const BREAKING: &str = r#"[
+            { "name": "scrap", "category": "scrap", "texture": "scrap.png", "size": [40, 40], "points": [0, 0] },
+            { "name": "rock", "category": "asteroid", "texture": "rock.png", "size": [60, 60], "damage": 1, "fragments": { "archetype": "pebble", "count": 3 } },
+            { "name": "pebble", "category": "asteroid", "texture": "pebble.png", "size": [30, 30], "damage": 1, "fall_speed": 1.5, "spawn_weight": 0 }
+        ]"#;
+        let world = vec2(800.0, 600.0);
+        let player = Player::new(Texture2D::empty(), world);
+        let mut simulation = Simulation::new(world, 3, player, test_content(BREAKING), Preset::Normal);
+        simulation.restart();
+        let step = simulation.difficulty.player_speed.value(simulation.progress()) * DT;
+        let (mut hits, mut destroyed) = (0, 0);
+        for tick in 0..60 * 60 {
+            // The ship's own bolts never meet anything in the lane, so rocks are shot down from elsewhere.
+            if tick % 20 == 0 {
+                if let Some(rock) = simulation.asteroids.iter_mut().find(|asteroid| asteroid.active && asteroid.archetype == 1) {
+                    rock.take_hit(BOLT_DAMAGE);
+                }
+            }
+            let offset = match simulation.director.lane() {
+                Some(lane) => lane - simulation.player.center().x,
+                None => 0.0,
+            };
+            let input = InputSnapshot { left: offset < -step, right: offset > step, ..Default::default() };
+            for event in simulation.step(&input, DT) {
+                match event {
+                    SimEvent::Hit { .. } => hits += 1,
+                    SimEvent::Destroyed { .. } => destroyed += 1,
+                    _ => {},
+                }
+            }
+        }
+        assert_eq!(hits, 0);
+        assert!(destroyed > 10, "only {} asteroids were shot", destroyed);
+    }
+
+    // Where every object in play is and what it is, in pool order.
+    fn snapshot(simulation: &Simulation) -> Vec<(usize, f32, f32, f32)> {
+        simulation.scraps.iter().chain(simulation.asteroids.iter()).chain(simulation.power_ups.iter())
+            .filter(|object| object.active)
+            .map(|object| (object.archetype, object.position.x, object.position.y, object.rotate))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_plays_out_the_same() {
+        let mut first = simulation();
+        let mut second = simulation();
+        let mut spawned = 0;
+        for tick in 0..600 {
+            let input = InputSnapshot { left: tick % 90 < 30, right: tick % 90 >= 60, ..Default::default() };
+            assert_eq!(first.step(&input, DT), second.step(&input, DT));
+            let objects = snapshot(&first);
+            assert_eq!(objects, snapshot(&second), "the runs split up at tick {}", tick);
+            spawned = spawned.max(objects.len());
+        }
+        assert!(spawned > 5);
+        assert_eq!(first.player.points, second.player.points);
+    }
+}
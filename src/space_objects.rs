@@ -1,35 +1,129 @@
-use macroquad::{prelude::{Rect, Texture2D, screen_width}, rand::gen_range, audio::{PlaySoundParams, Sound, play_sound}};
+use macroquad::{prelude::{Rect, Vec2}, rand::RandGenerator};
+use std::rc::Rc;
+use crate::{content::Archetype, collision::{Shape, ShapeScratch}};
 
 pub struct SpaceObject {
     pub position: Rect,
+    pub archetype: usize,
     pub points: i32,
-    pub texture: Texture2D,
     pub rotate: f32,
-    pub health: i32
+    pub rotation_speed: f32,
+    pub fall_speed: f32,
+    pub shape: Rc<Shape>,
+    // Hits left before it breaks, and what it does to the ship.
+    pub health: i32,
+    pub damage: i32,
+    // Sideways speed in pixels per second, for fragments flying apart.
+    pub drift: f32,
+    pub active: bool,
+    previous: Vec2,
+    previous_rotate: f32
 }
 
 impl SpaceObject {
-    pub fn new(texture: Texture2D) -> Self {
-        Self{ position: Rect { x: gen_range(0.0, screen_width() - 64.0), y: gen_range(0.0, 50.0), w: 64.0, h: 64.0 }, points: gen_range(1, 5), texture: texture, rotate: gen_range(0.0, 360.0), health: -1 }
+    // Objects start out asleep in their pool until the director spawns them.
+    pub fn new(index: usize, archetype: &Archetype) -> Self {
+        Self{ position: Rect { x: 0.0, y: 0.0, w: archetype.size[0], h: archetype.size[1] }, archetype: index, points: 0, rotate: 0.0, rotation_speed: 0.0, fall_speed: 1.0, shape: Rc::clone(&archetype.collision), health: 0, damage: 0, drift: 0.0, active: false, previous: Vec2::ZERO, previous_rotate: 0.0 }
     }
-    pub fn reset(&mut self) {
-        self.position.x = gen_range(0.0, screen_width());
-        self.position.y = gen_range(0.0, 50.0);
-        self.rotate = gen_range(0.0, 360.0);
+    // Wakes the object up as the given archetype, centered on `x` just above the top of the screen.
+    pub fn spawn(&mut self, index: usize, archetype: &Archetype, x: f32, world: Vec2, rng: &RandGenerator) {
+        self.archetype = index;
+        self.position.w = archetype.size[0];
+        self.position.h = archetype.size[1];
+        self.shape = Rc::clone(&archetype.collision);
+        self.points = rng.gen_range(archetype.points[0], archetype.points[1] + 1);
+        self.health = archetype.hit_points;
+        self.damage = archetype.damage;
+        self.drift = 0.0;
+        self.fall_speed = archetype.fall_speed;
+        self.rotation_speed = archetype.rotation_speed.to_radians();
+        self.position.x = (x - self.position.w / 2.0).clamp(0.0, (world.x - self.position.w).max(0.0));
+        self.position.y = -self.position.h;
+        self.rotate = rng.gen_range(0.0, std::f32::consts::TAU);
+        self.active = true;
+        self.begin_tick();
     }
-    pub fn hit(&mut self, sound: Sound, volume: f32) {
-        play_sound(sound, PlaySoundParams { looped: false, volume: volume});
-        self.position.x = gen_range(0.0, screen_width() - self.position.w);
-        self.position.y = gen_range(0.0, 50.0);
-        self.rotate = gen_range(0.0, 360.0);
+    pub fn despawn(&mut self) {
+        self.active = false;
+    }
+    // Moves a freshly spawned object so it is centered on `center` instead of above the screen.
+    pub fn place(&mut self, center: Vec2, world: Vec2) {
+        self.position.x = (center.x - self.position.w / 2.0).clamp(0.0, (world.x - self.position.w).max(0.0));
+        self.position.y = center.y - self.position.h / 2.0;
+        self.begin_tick();
+    }
+    pub fn take_hit(&mut self, damage: i32) {
+        self.health -= damage;
+    }
+    pub fn is_destroyed(&self) -> bool {
+        self.health <= 0
+    }
+    pub fn is_off_screen(&self, world: Vec2) -> bool {
+        self.position.y > world.y || self.position.right() < 0.0 || self.position.x > world.x
+    }
+    pub fn center(&self) -> Vec2 {
+        self.position.center()
+    }
+    // How far its shape can reach from its center, whichever way it is turned.
+    pub fn reach(&self) -> f32 {
+        self.position.w.hypot(self.position.h) / 2.0
+    }
+    // The horizontal span it can cover for the rest of its fall. Drifting
+    // objects can end up anywhere up to the edge they drift towards.
+    pub fn fall_column(&self, world: Vec2) -> (f32, f32) {
+        let (center, reach) = (self.center().x, self.reach());
+        if self.drift > 0.0 {
+            (center - reach, world.x + reach)
+        }
+        else if self.drift < 0.0 {
+            (-reach, center + reach)
+        }
+        else {
+            (center - reach, center + reach)
+        }
+    }
+    // Remembers where the object was before this tick so drawing can blend between ticks.
+    pub fn begin_tick(&mut self) {
+        self.previous = self.position.point();
+        self.previous_rotate = self.rotate;
+    }
+    pub fn draw_rect(&self, alpha: f32) -> Rect {
+        let point = self.previous.lerp(self.position.point(), alpha);
+        Rect { x: point.x, y: point.y, w: self.position.w, h: self.position.h }
+    }
+    pub fn draw_rotation(&self, alpha: f32) -> f32 {
+        self.previous_rotate + (self.rotate - self.previous_rotate) * alpha
+    }
+    // Where the object was at the start of this tick and how far it has moved since.
+    pub fn previous_shape<'a>(&self, scratch: &'a mut ShapeScratch) -> &'a Shape {
+        scratch.place(&self.shape, self.draw_rect(0.0), self.previous_rotate)
+    }
+    pub fn motion(&self) -> Vec2 {
+        self.position.point() - self.previous
     }
     pub fn get_points(&self) -> i32 {
         self.points.clone()
     }
-    pub fn get_health(&self) -> i32 {
-        self.health.clone()
+    pub fn get_damage(&self) -> i32 {
+        self.damage
     }
     pub fn move_y(&mut self, speed: f32) {
         self.position.y += speed;
     }
+    pub fn apply_drift(&mut self, dt: f32) {
+        self.position.x += self.drift * dt;
+    }
+    // Moves up to `step` towards `target` when it is within `radius`.
+    pub fn pull_towards(&mut self, target: Vec2, radius: f32, step: f32) {
+        let offset = target - self.position.center();
+        let distance = offset.length();
+        if distance > 0.0 && distance < radius {
+            let moved = offset / distance * step.min(distance);
+            self.position.x += moved.x;
+            self.position.y += moved.y;
+        }
+    }
+    pub fn spin(&mut self, dt: f32) {
+        self.rotate += self.rotation_speed * dt;
+    }
 }
\ No newline at end of file
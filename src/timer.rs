@@ -1,24 +1,406 @@
 use macroquad::prelude::get_time;
 
+pub trait Clock {
+    fn now(&self) -> f64;
+}
+
+// Wall time since the window opened.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        get_time()
+    }
+}
+
+// Only moves when advanced, used by the simulation and in tests.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ManualClock {
+    time: f64,
+}
+
+impl ManualClock {
+    pub fn new(time: f64) -> Self {
+        Self { time }
+    }
+
+    pub fn advance(&mut self, dt: f64) {
+        self.time += dt;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> f64 {
+        self.time
+    }
+}
+
+// Wraps another clock and stands still while paused, e.g. while the game's pause menu is open.
+pub struct PausableClock<C: Clock> {
+    inner: C,
+    paused_at: Option<f64>,
+    paused_total: f64,
+}
+
+impl<C: Clock> PausableClock<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner, paused_at: None, paused_total: 0.0 }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused {
+            self.pause();
+        }
+        else {
+            self.resume();
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(self.inner.now());
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused_total += self.inner.now() - paused_at;
+        }
+    }
+}
+
+impl<C: Clock> Clock for PausableClock<C> {
+    fn now(&self) -> f64 {
+        self.paused_at.unwrap_or_else(|| self.inner.now()) - self.paused_total
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimerMode {
+    Once,
+    Repeating,
+}
+
+#[derive(Clone, Debug)]
 pub struct Timer {
-    pub start_time: f64,
-    pub life_time:f64,
-    pub first_time: bool,
+    pub duration: f64,
+    pub mode: TimerMode,
+    start: f64,
+    paused_at: Option<f64>,
+    fired: bool,
 }
 
 impl Timer {
-    pub fn new(time: f64, intial: bool) -> Timer {
-        Timer{ start_time: get_time(), life_time: time, first_time: intial }
+    pub fn new(duration: f64, mode: TimerMode, clock: &impl Clock) -> Timer {
+        Timer { duration, mode, start: clock.now(), paused_at: None, fired: false }
+    }
+
+    pub fn once(duration: f64, clock: &impl Clock) -> Timer {
+        Timer::new(duration, TimerMode::Once, clock)
+    }
+
+    pub fn repeating(duration: f64, clock: &impl Clock) -> Timer {
+        Timer::new(duration, TimerMode::Repeating, clock)
+    }
+
+    // A one-shot timer that has already run out.
+    pub fn finished(clock: &impl Clock) -> Timer {
+        Timer { duration: 0.0, mode: TimerMode::Once, start: clock.now(), paused_at: None, fired: true }
+    }
+
+    pub fn elapsed(&self, clock: &impl Clock) -> f64 {
+        self.paused_at.unwrap_or_else(|| clock.now()) - self.start
+    }
+
+    pub fn remaining(&self, clock: &impl Clock) -> f64 {
+        (self.duration - self.elapsed(clock)).max(0.0)
     }
 
-    pub fn is_timer_done(&self) -> bool {
-        let current_time = get_time();
-        let time_elapsed = current_time - self.start_time;
-        if time_elapsed == self.life_time {
-            true
+    pub fn progress(&self, clock: &impl Clock) -> f64 {
+        if self.duration <= 0.0 {
+            return 1.0;
         }
-        else {
-            false
+        (self.elapsed(clock) / self.duration).clamp(0.0, 1.0)
+    }
+
+    pub fn is_done(&self, clock: &impl Clock) -> bool {
+        self.elapsed(clock) >= self.duration
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn pause(&mut self, clock: &impl Clock) {
+        if !self.is_paused() {
+            self.paused_at = Some(clock.now());
+        }
+    }
+
+    pub fn resume(&mut self, clock: &impl Clock) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.start += clock.now() - paused_at;
+        }
+    }
+
+    pub fn restart(&mut self, clock: &impl Clock) {
+        self.start = clock.now();
+        self.paused_at = None;
+        self.fired = false;
+    }
+
+    // Returns true once each time the timer runs out. Repeating timers carry
+    // any overshoot into the next period so they don't drift.
+    pub fn poll(&mut self, clock: &impl Clock) -> bool {
+        if !self.is_done(clock) {
+            return false;
         }
+        match self.mode {
+            TimerMode::Once => {
+                let first = !self.fired;
+                self.fired = true;
+                first
+            },
+            TimerMode::Repeating => {
+                if self.duration > 0.0 {
+                    let periods = (self.elapsed(clock) / self.duration).floor();
+                    self.start += periods * self.duration;
+                }
+                true
+            },
+        }
+    }
+}
+
+// Fires events after a delay or on an interval. Anything can be used as the
+// event, including boxed callbacks.
+pub struct Scheduler<E> {
+    entries: Vec<(Timer, E)>,
+}
+
+impl<E: Clone> Scheduler<E> {
+    pub fn new() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    pub fn after(&mut self, delay: f64, event: E, clock: &impl Clock) {
+        self.entries.push((Timer::once(delay, clock), event));
+    }
+
+    pub fn every(&mut self, interval: f64, event: E, clock: &impl Clock) {
+        self.entries.push((Timer::repeating(interval, clock), event));
+    }
+
+    pub fn pause(&mut self, clock: &impl Clock) {
+        for (timer, _) in self.entries.iter_mut() {
+            timer.pause(clock);
+        }
+    }
+
+    pub fn resume(&mut self, clock: &impl Clock) {
+        for (timer, _) in self.entries.iter_mut() {
+            timer.resume(clock);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn poll(&mut self, clock: &impl Clock) -> Vec<E> {
+        let mut fired = Vec::new();
+        for (timer, event) in self.entries.iter_mut() {
+            if timer.poll(clock) {
+                fired.push(event.clone());
+            }
+        }
+        self.entries.retain(|(timer, _)| timer.mode == TimerMode::Repeating || !timer.fired);
+        fired
+    }
+}
+
+impl<E: Clone + PartialEq> Scheduler<E> {
+    // Seconds until the first pending `event` fires, if one is scheduled.
+    pub fn remaining(&self, event: &E, clock: &impl Clock) -> Option<f64> {
+        self.entries.iter()
+            .filter(|(_, scheduled)| scheduled == event)
+            .map(|(timer, _)| timer.remaining(clock))
+            .reduce(f64::min)
+    }
+}
+
+// Turns variable frame times into a whole number of fixed simulation ticks.
+// Leftover time carries over to the next frame, and `alpha` says how far we
+// are between the last two ticks so rendering can interpolate.
+pub struct FixedTimestep {
+    pub tick: f64,
+    pub max_ticks: u32,
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    pub fn new(tick_rate: u32, max_ticks: u32) -> Self {
+        Self { tick: 1.0 / tick_rate.max(1) as f64, max_ticks, accumulator: 0.0 }
     }
-}
\ No newline at end of file
+
+    pub fn set_tick_rate(&mut self, tick_rate: u32) {
+        self.tick = 1.0 / tick_rate.max(1) as f64;
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+
+    // Returns how many ticks to run this frame. After a long hitch the backlog
+    // is dropped instead of being caught up, so the game slows down rather
+    // than freezing while it simulates seconds of play.
+    pub fn advance(&mut self, frame_time: f64) -> u32 {
+        self.accumulator += frame_time.max(0.0);
+        let mut ticks = 0;
+        while self.accumulator >= self.tick && ticks < self.max_ticks {
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+        if ticks == self.max_ticks {
+            self.accumulator = self.accumulator.min(self.tick);
+        }
+        ticks
+    }
+
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick).clamp(0.0, 1.0) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paused_clock_stands_still() {
+        let mut clock = PausableClock::new(ManualClock::new(10.0));
+        let timer = Timer::once(2.0, &clock);
+        clock.inner.advance(1.0);
+        clock.pause();
+        clock.inner.advance(5.0);
+        assert_eq!(clock.now(), 11.0);
+        assert!(!timer.is_done(&clock));
+        // Pausing twice keeps the first pause time.
+        clock.set_paused(true);
+        clock.resume();
+        assert_eq!(clock.now(), 11.0);
+        clock.inner.advance(1.0);
+        assert_eq!(timer.elapsed(&clock), 2.0);
+        assert!(timer.is_done(&clock));
+    }
+
+    #[test]
+    fn once_timer_fires_once() {
+        let mut clock = ManualClock::new(0.0);
+        let mut timer = Timer::once(1.0, &clock);
+        assert!(!timer.poll(&clock));
+        clock.advance(1.5);
+        assert!(timer.poll(&clock));
+        assert!(!timer.poll(&clock));
+        timer.restart(&clock);
+        assert_eq!(timer.progress(&clock), 0.0);
+    }
+
+    #[test]
+    fn repeating_timer_keeps_its_rhythm() {
+        let mut clock = ManualClock::new(0.0);
+        let mut timer = Timer::repeating(1.0, &clock);
+        clock.advance(2.5);
+        assert!(timer.poll(&clock));
+        assert_eq!(timer.elapsed(&clock), 0.5);
+        clock.advance(0.5);
+        assert!(timer.poll(&clock));
+    }
+
+    #[test]
+    fn paused_timer_keeps_its_remaining_time() {
+        let mut clock = ManualClock::new(0.0);
+        let mut timer = Timer::once(2.0, &clock);
+        clock.advance(0.5);
+        timer.pause(&clock);
+        assert!(timer.is_paused());
+        clock.advance(10.0);
+        assert_eq!(timer.remaining(&clock), 1.5);
+        timer.resume(&clock);
+        clock.advance(1.0);
+        assert_eq!(timer.remaining(&clock), 0.5);
+        assert!(!timer.poll(&clock));
+        clock.advance(1.0);
+        assert_eq!(timer.remaining(&clock), 0.0);
+        assert!(timer.poll(&clock));
+    }
+
+    #[test]
+    fn scheduler_fires_events_in_time() {
+        let mut clock = ManualClock::new(0.0);
+        let mut scheduler = Scheduler::new();
+        scheduler.after(1.0, "once", &clock);
+        scheduler.every(0.75, "every", &clock);
+        clock.advance(0.8);
+        assert_eq!(scheduler.poll(&clock), vec!["every"]);
+        clock.advance(0.3);
+        assert_eq!(scheduler.poll(&clock), vec!["once"]);
+        assert_eq!(scheduler.remaining(&"once", &clock), None);
+        assert!((scheduler.remaining(&"every", &clock).unwrap() - 0.4).abs() < 1e-9);
+        scheduler.clear();
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn paused_scheduler_holds_its_events() {
+        let mut clock = ManualClock::new(0.0);
+        let mut scheduler = Scheduler::new();
+        scheduler.after(1.0, 1, &clock);
+        scheduler.pause(&clock);
+        clock.advance(5.0);
+        assert!(scheduler.poll(&clock).is_empty());
+        scheduler.resume(&clock);
+        clock.advance(1.0);
+        assert_eq!(scheduler.poll(&clock), vec![1]);
+        assert!(scheduler.poll(&clock).is_empty());
+    }
+
+    // Steps a fixed timestep by whatever time passed on `clock` since `last`.
+    fn frame(timestep: &mut FixedTimestep, clock: &ManualClock, last: &mut f64) -> u32 {
+        let frame_time = clock.now() - *last;
+        *last = clock.now();
+        timestep.advance(frame_time)
+    }
+
+    #[test]
+    fn long_hitch_drops_the_backlog() {
+        let mut clock = ManualClock::new(0.0);
+        let mut last = clock.now();
+        let mut timestep = FixedTimestep::new(120, 8);
+        clock.advance(1.0);
+        assert_eq!(frame(&mut timestep, &clock, &mut last), 8);
+        // Only up to one tick is kept, not the other 112.
+        assert!(timestep.alpha() <= 1.0);
+        assert_eq!(frame(&mut timestep, &clock, &mut last), 1);
+        assert_eq!(frame(&mut timestep, &clock, &mut last), 0);
+    }
+
+    #[test]
+    fn leftover_time_sets_alpha() {
+        let mut clock = ManualClock::new(0.0);
+        let mut last = clock.now();
+        let mut timestep = FixedTimestep::new(120, 8);
+        clock.advance(2.5 / 120.0);
+        assert_eq!(frame(&mut timestep, &clock, &mut last), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+        clock.advance(0.75 / 120.0);
+        assert_eq!(frame(&mut timestep, &clock, &mut last), 1);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-4);
+        timestep.reset();
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+}
@@ -0,0 +1,66 @@
+use macroquad::prelude::{Vec2, vec2};
+use crate::collision::Shape;
+
+// Pixels per second, straight up.
+const BOLT_SPEED: f32 = 900.0;
+const BOLT_RADIUS: f32 = 5.0;
+pub const BOLT_DAMAGE: i32 = 1;
+const BOLT_POOL_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Bolt {
+    pub position: Vec2,
+    pub active: bool,
+    previous: Vec2,
+}
+
+impl Bolt {
+    pub fn draw_position(&self, alpha: f32) -> Vec2 {
+        self.previous.lerp(self.position, alpha)
+    }
+
+    pub fn previous_shape(&self) -> Shape {
+        Shape::Circle { center: self.previous, radius: BOLT_RADIUS }
+    }
+
+    pub fn motion(&self) -> Vec2 {
+        self.position - self.previous
+    }
+}
+
+// The ship's cannon, bought in the shop. The simulation fires it on its own every
+// `fire_interval` seconds of the loadout, so it needs no input and replays stay exact.
+pub struct Cannon {
+    pub bolts: Vec<Bolt>,
+}
+
+impl Cannon {
+    pub fn new() -> Self {
+        Self { bolts: vec![Bolt { position: Vec2::ZERO, active: false, previous: Vec2::ZERO }; BOLT_POOL_SIZE] }
+    }
+
+    pub fn reset(&mut self) {
+        for bolt in self.bolts.iter_mut() {
+            bolt.active = false;
+        }
+    }
+
+    // Moves the bolts in flight and drops the ones that left the screen.
+    pub fn update(&mut self, dt: f32) {
+        for bolt in self.bolts.iter_mut().filter(|bolt| bolt.active) {
+            bolt.previous = bolt.position;
+            bolt.position.y -= BOLT_SPEED * dt;
+            if bolt.position.y < -BOLT_RADIUS {
+                bolt.active = false;
+            }
+        }
+    }
+
+    // Shoots a bolt from `muzzle` that has already flown for this tick. Does nothing
+    // while every bolt is still in flight.
+    pub fn fire(&mut self, muzzle: Vec2, dt: f32) {
+        if let Some(bolt) = self.bolts.iter_mut().find(|bolt| !bolt.active) {
+            *bolt = Bolt { position: muzzle - vec2(0.0, BOLT_SPEED * dt), active: true, previous: muzzle };
+        }
+    }
+}